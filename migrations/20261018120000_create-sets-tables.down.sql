@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS sets_elements;
+DROP TABLE IF EXISTS sets;
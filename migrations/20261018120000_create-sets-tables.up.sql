@@ -0,0 +1,26 @@
+CREATE TABLE IF NOT EXISTS sets (
+    tid         VARCHAR(40) NOT NULL,
+    id          VARCHAR(256) NOT NULL,
+    name        VARCHAR(80) NOT NULL,
+    created_at  TIMESTAMP NOT NULL,
+
+    CONSTRAINT sets_pkey PRIMARY KEY (tid, id),
+    CONSTRAINT sets_tid_fkey FOREIGN KEY (tid) REFERENCES tenants (id)
+);
+
+CREATE INDEX IF NOT EXISTS sets_tid_created_at_idx ON sets (tid, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS sets_elements (
+    tid         VARCHAR(40) NOT NULL,
+    sid         VARCHAR(256) NOT NULL,
+    eid         VARCHAR(256) NOT NULL,
+    created_at  TIMESTAMP NOT NULL,
+
+    CONSTRAINT sets_elements_pkey PRIMARY KEY (tid, sid, eid),
+    CONSTRAINT sets_elements_sid_fkey FOREIGN KEY (tid, sid)
+        REFERENCES sets (tid, id) ON DELETE CASCADE,
+    CONSTRAINT sets_elements_eid_fkey FOREIGN KEY (tid, eid)
+        REFERENCES elements (tid, id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS sets_elements_tid_eid_idx ON sets_elements (tid, eid);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS relationships (
+    tid         VARCHAR(40) NOT NULL,
+    from_id     VARCHAR(256) NOT NULL,
+    rel_type    VARCHAR(80) NOT NULL,
+    to_id       VARCHAR(256) NOT NULL,
+    data        JSONB NOT NULL,
+    created_at  TIMESTAMP NOT NULL,
+
+    CONSTRAINT relationships_pkey PRIMARY KEY (tid, from_id, rel_type, to_id),
+    CONSTRAINT relationships_from_fkey FOREIGN KEY (tid, from_id) REFERENCES elements (tid, id),
+    CONSTRAINT relationships_to_fkey FOREIGN KEY (tid, to_id) REFERENCES elements (tid, id)
+);
+
+CREATE INDEX IF NOT EXISTS relationships_tid_to_id_idx ON relationships (tid, to_id, rel_type);
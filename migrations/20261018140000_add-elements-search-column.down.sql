@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS elements_search_idx;
+
+ALTER TABLE elements DROP COLUMN IF EXISTS search;
@@ -0,0 +1,4 @@
+ALTER TABLE elements ADD COLUMN IF NOT EXISTS search TSVECTOR
+    GENERATED ALWAYS AS (jsonb_to_tsvector('simple'::REGCONFIG, data, '["string", "numeric"]')) STORED;
+
+CREATE INDEX IF NOT EXISTS elements_search_idx ON elements USING GIN (search);
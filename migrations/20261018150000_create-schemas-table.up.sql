@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS schemas (
+    tid         VARCHAR(40) NOT NULL,
+    sid         VARCHAR(256),
+    schema      JSONB NOT NULL,
+    created_at  TIMESTAMP NOT NULL,
+
+    CONSTRAINT schemas_tid_fkey FOREIGN KEY (tid)
+        REFERENCES tenants (id) ON DELETE CASCADE,
+    CONSTRAINT schemas_sid_fkey FOREIGN KEY (tid, sid)
+        REFERENCES sets (tid, id) ON DELETE CASCADE
+);
+
+-- Only one schema for the whole tenant (sid IS NULL), and one for each set
+CREATE UNIQUE INDEX IF NOT EXISTS schemas_tid_idx ON schemas (tid) WHERE sid IS NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS schemas_tid_sid_idx ON schemas (tid, sid) WHERE sid IS NOT NULL;
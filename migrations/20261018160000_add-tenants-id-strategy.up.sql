@@ -0,0 +1,3 @@
+ALTER TABLE tenants ADD COLUMN IF NOT EXISTS id_strategy VARCHAR(20) NOT NULL DEFAULT 'random';
+-- Last value used by the "sequence" id strategy
+ALTER TABLE tenants ADD COLUMN IF NOT EXISTS id_seq BIGINT NOT NULL DEFAULT 0;
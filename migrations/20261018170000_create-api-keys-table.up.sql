@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS api_keys (
+    id          VARCHAR(40) NOT NULL,
+    -- NULL for admin keys, that have access to all the tenants
+    tid         VARCHAR(40),
+    secret_hash VARCHAR(64) NOT NULL,
+    scopes      VARCHAR(20)[] NOT NULL,
+    created_at  TIMESTAMP NOT NULL,
+
+    CONSTRAINT api_keys_pkey PRIMARY KEY (id),
+    CONSTRAINT api_keys_tid_fkey FOREIGN KEY (tid)
+        REFERENCES tenants (id) ON DELETE CASCADE
+);
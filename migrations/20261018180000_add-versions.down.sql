@@ -0,0 +1,2 @@
+ALTER TABLE elements DROP COLUMN IF EXISTS version;
+ALTER TABLE tenants DROP COLUMN IF EXISTS version;
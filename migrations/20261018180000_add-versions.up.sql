@@ -0,0 +1,3 @@
+-- Version of the rows, incremented on each update and used as ETag
+ALTER TABLE tenants ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE elements ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
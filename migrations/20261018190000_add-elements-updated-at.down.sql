@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS elements_tid_updated_at_idx;
+ALTER TABLE elements DROP COLUMN IF EXISTS updated_by;
+ALTER TABLE elements DROP COLUMN IF EXISTS updated_at;
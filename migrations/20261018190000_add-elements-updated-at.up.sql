@@ -0,0 +1,7 @@
+ALTER TABLE elements ADD COLUMN IF NOT EXISTS updated_at TIMESTAMP;
+UPDATE elements SET updated_at = created_at WHERE updated_at IS NULL;
+ALTER TABLE elements ALTER COLUMN updated_at SET NOT NULL;
+-- Id of the principal that updated the element last, if authenticated
+ALTER TABLE elements ADD COLUMN IF NOT EXISTS updated_by VARCHAR(256);
+
+CREATE INDEX IF NOT EXISTS elements_tid_updated_at_idx ON elements (tid, updated_at);
@@ -0,0 +1,17 @@
+-- Prior versions of the elements, written when an element is updated or deleted
+CREATE TABLE IF NOT EXISTS element_revisions (
+    tid         VARCHAR(40) NOT NULL,
+    id          VARCHAR(256) NOT NULL,
+    version     BIGINT NOT NULL,
+    data        JSONB NOT NULL,
+    created_at  TIMESTAMP NOT NULL,
+    updated_at  TIMESTAMP NOT NULL,
+    updated_by  VARCHAR(256),
+    -- Time when the version stopped being the current one
+    replaced_at TIMESTAMP NOT NULL,
+    -- Whether the version was replaced by deleting the element
+    deleted     BOOLEAN NOT NULL DEFAULT FALSE,
+
+    CONSTRAINT element_revisions_pkey PRIMARY KEY (tid, id, version),
+    CONSTRAINT element_revisions_tid_fkey FOREIGN KEY (tid) REFERENCES tenants (id)
+);
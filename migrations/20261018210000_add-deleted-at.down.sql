@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS elements_deleted_at_idx;
+ALTER TABLE element_revisions DROP COLUMN IF EXISTS deleted_at;
+ALTER TABLE elements DROP COLUMN IF EXISTS deleted_at;
+ALTER TABLE tenants DROP COLUMN IF EXISTS deleted_at;
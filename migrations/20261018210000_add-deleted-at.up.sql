@@ -0,0 +1,6 @@
+-- Time when the tenant or element was moved to the trash (soft deleted)
+ALTER TABLE tenants ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP;
+ALTER TABLE elements ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP;
+ALTER TABLE element_revisions ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP;
+
+CREATE INDEX IF NOT EXISTS elements_deleted_at_idx ON elements (deleted_at) WHERE deleted_at IS NOT NULL;
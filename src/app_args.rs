@@ -1,8 +1,13 @@
+use crate::auth::Scope;
+use crate::tenants::model::IdStrategy;
+use crate::trash::parse_age;
+use chrono::TimeDelta;
 use clap::{Parser, Subcommand};
 use env_logger::Target;
 use log::{Level, LevelFilter};
 use std::env::var;
 use std::io::Write;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 #[derive(Parser)]
@@ -25,9 +30,13 @@ impl Args {
                 .target(Target::Stdout)
                 .filter_level(level_filter)
                 .init(),
-            // Commands use normal stdout, command-like style for output
+            // Commands use normal stdout, command-like style for output,
+            // except the export that writes the data exported in stdout
             _ => env_logger::builder()
-                .target(Target::Stdout)
+                .target(match &args.command {
+                    Commands::Export { .. } => Target::Stderr,
+                    _ => Target::Stdout,
+                })
                 .filter_level(level_filter)
                 .filter(Some("server_env_config"), LevelFilter::Warn)
                 .filter(Some("actix_contrib_rest::app_state"), LevelFilter::Warn)
@@ -49,7 +58,15 @@ pub enum Commands {
     /// Run the Backset HTTP Server
     Run,
     /// Check the HTTP Server health (whether it's running)
-    Health,
+    Health {
+        /// Check the DB connection of the server as well
+        #[arg(long)]
+        db: bool,
+
+        /// Max time to wait for the DB check, in milliseconds
+        #[arg(long, value_name = "MILLIS", requires = "db")]
+        timeout: Option<u64>,
+    },
     /// List objects
     List {
         #[command(subcommand)]
@@ -59,6 +76,28 @@ pub enum Commands {
         #[command(subcommand)]
         object: CreateObjects,
     },
+    /// Revoke objects
+    Revoke {
+        #[command(subcommand)]
+        object: RevokeObjects,
+    },
+    /// Export objects as NDJSON in the standard output
+    Export {
+        #[command(subcommand)]
+        object: ExportObjects,
+    },
+    /// Import objects exported with the export command
+    Import {
+        #[command(subcommand)]
+        object: ImportObjects,
+    },
+    /// Delete for good the tenants and elements in the trash (soft deleted)
+    Purge {
+        /// Only the ones moved to the trash before this time ago, e.g. "30d",
+        /// with the units s, m, h, d or w
+        #[arg(long, value_name = "AGE", value_parser = parse_age)]
+        older_than: TimeDelta,
+    },
 }
 
 #[derive(Subcommand, strum_macros::Display)]
@@ -73,6 +112,12 @@ pub enum ListObjects {
         #[arg(short = 'n', long, default_value_t = 1000, value_name = "MAX")]
         lines: i64,
     },
+    /// List API keys
+    ApiKeys {
+        /// List only the keys of the tenant
+        #[arg(short = 't', long)]
+        tenant: Option<String>,
+    },
     /// List all ENVIRONMENT_VARIABLE=current_value used by the server
     Envs,
 }
@@ -88,5 +133,58 @@ pub enum CreateObjects {
         /// The name of the new tenant
         #[arg(short = 'n', long)]
         name: String,
+
+        /// Strategy to generate the ids of the elements: random, uuidv4,
+        /// uuidv7, ulid, sequence or hash
+        #[arg(short = 's', long, default_value_t = IdStrategy::Random, value_name = "STRATEGY")]
+        id_strategy: IdStrategy,
+    },
+    /// Create API key, the token to use it is only displayed once
+    ApiKey {
+        /// The tenant the key has access to
+        #[arg(short = 't', long, required_unless_present = "admin")]
+        tenant: Option<String>,
+
+        /// Create an admin key, with access to all the tenants
+        #[arg(long, conflicts_with = "tenant")]
+        admin: bool,
+
+        /// Scopes of the key separated by commas: read and write
+        #[arg(short = 's', long = "scope", value_delimiter = ',', default_value = "read", value_name = "SCOPES")]
+        scopes: Vec<Scope>,
+    },
+}
+
+#[derive(Subcommand, strum_macros::Display)]
+pub enum RevokeObjects {
+    /// Revoke API key
+    ApiKey {
+        /// The id of the key
+        #[clap(value_name = "ID")]
+        id: String,
+    },
+}
+
+#[derive(Subcommand, strum_macros::Display)]
+pub enum ExportObjects {
+    /// Export tenant with all its elements
+    Tenant {
+        /// The id of the tenant
+        #[clap(value_name = "ID")]
+        id: String,
+    },
+}
+
+#[derive(Subcommand, strum_macros::Display)]
+pub enum ImportObjects {
+    /// Import tenant with all its elements, the tenant cannot exist
+    Tenant {
+        /// The file with the tenant exported
+        #[clap(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Import the tenant with another id than the exported one
+        #[arg(long)]
+        id: Option<String>,
     },
 }
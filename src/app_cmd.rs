@@ -1,5 +1,14 @@
-use crate::app_args::{Commands, CreateObjects, ListObjects};
-use crate::tenants::model::{Tenant, TenantPayload};
+use crate::app_args::{
+    Commands, CreateObjects, ExportObjects, ImportObjects, ListObjects, RevokeObjects,
+};
+use crate::app_server::DEFAULT_SHUTDOWN_GRACE_PERIOD;
+use crate::auth::model::ApiKey;
+use crate::auth::{AuthMode, Scope};
+use crate::health::{DbHealth, DEFAULT_DB_TIMEOUT_MILLIS};
+use crate::telemetry::TraceExporter;
+use crate::tenants::export::{Exporter, Importer};
+use crate::tenants::model::{IdStrategy, Tenant, TenantPayload};
+use crate::trash::purge;
 use actix_contrib_rest::app_state::AppState;
 use actix_contrib_rest::query::QuerySearch;
 use actix_contrib_rest::result::AppError;
@@ -7,12 +16,17 @@ use actix_contrib_rest::result::Result;
 use actix_contrib_rest::stream::read_body;
 use actix_web::http::header::Accept;
 use awc::Client;
+use chrono::TimeDelta;
 use log::{error, info};
 use serde::Deserialize;
 use server_env_config::Config;
 use sqlx::Connection;
 use std::env;
+use std::fs::File;
+use std::io::{stdout, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
 use std::process::exit;
+use std::time::Duration;
 use validator::Validate;
 
 /// App state class for command line instructions, instead of the HTTP server.
@@ -25,6 +39,7 @@ pub struct HealthPayload {
     #[serde(default = "String::new")]
     pub service: String,
     pub version: Option<String>,
+    pub db: Option<DbHealth>,
 }
 
 impl AppCmd {
@@ -35,8 +50,8 @@ impl AppCmd {
 
     pub async fn run(&self, cmd: &Commands) -> Result<()> {
         match cmd {
-            Commands::Health => {
-                self.healthcheck().await?;
+            Commands::Health { db, timeout } => {
+                self.healthcheck(*db, *timeout).await?;
             }
             Commands::List { object: ListObjects::Envs } => {
                 self.list_envs();
@@ -44,8 +59,26 @@ impl AppCmd {
             Commands::List { object: ListObjects::Tenants { query, lines } } => {
                 self.list_tenants(query, *lines).await?;
             }
-            Commands::Create { object: CreateObjects::Tenant { id, name } } => {
-                self.create_tenant(id, name).await?;
+            Commands::List { object: ListObjects::ApiKeys { tenant } } => {
+                self.list_api_keys(tenant.as_deref()).await?;
+            }
+            Commands::Create { object: CreateObjects::Tenant { id, name, id_strategy } } => {
+                self.create_tenant(id, name, *id_strategy).await?;
+            }
+            Commands::Create { object: CreateObjects::ApiKey { tenant, admin: _, scopes } } => {
+                self.create_api_key(tenant.as_deref(), scopes).await?;
+            }
+            Commands::Revoke { object: RevokeObjects::ApiKey { id } } => {
+                self.revoke_api_key(id).await?;
+            }
+            Commands::Export { object: ExportObjects::Tenant { id } } => {
+                self.export_tenant(id).await?;
+            }
+            Commands::Import { object: ImportObjects::Tenant { file, id } } => {
+                self.import_tenant(file, id.as_deref()).await?;
+            }
+            Commands::Purge { older_than } => {
+                self.purge(*older_than).await?;
             }
             Commands::Run => {
                 // It should not get to this point
@@ -56,9 +89,18 @@ impl AppCmd {
         Ok(())
     }
 
-    async fn healthcheck(&self) -> Result<()> {
-        let client = Client::default();
-        let health_url = format!("{}{}", &self.state.config.server.url, "health");
+    async fn healthcheck(&self, db: bool, timeout: Option<u64>) -> Result<()> {
+        let mut health_url = format!("{}{}", &self.state.config.server.url, "health");
+        if db {
+            health_url.push_str("?db=true");
+            if let Some(timeout) = timeout {
+                health_url.push_str(format!("&timeout={timeout}").as_str());
+            }
+        }
+        // Wait a bit longer than the DB check, so it's reported by the server
+        let client = Client::builder()
+            .timeout(Duration::from_millis(timeout.unwrap_or(DEFAULT_DB_TIMEOUT_MILLIS) + 1000))
+            .finish();
         info!("Checking backset at {} ...", health_url);
         let mut res = client
             .get(&health_url)
@@ -80,6 +122,15 @@ impl AppCmd {
                 "backset" => {
                     info!("{}", res.status());
                     info!("Backset version: {}", val.version.as_deref().unwrap_or(""));
+                    if let Some(db) = val.db {
+                        info!(
+                            "DB: {}, latency: {:.1}ms, pool size: {}, idle: {}",
+                            db.status,
+                            db.latency_ms.unwrap_or_default(),
+                            db.pool_size.unwrap_or_default(),
+                            db.pool_idle.unwrap_or_default(),
+                        );
+                    }
                 }
                 _ => {
                     error!("Unexpected response, looks like it's not backset.");
@@ -109,6 +160,8 @@ impl AppCmd {
                 sort: Some("id".to_string()),
                 include_total: Some(false),
             },
+            None,
+            false,
         )
         .await?;
         self.state.commit_tx(tx).await?;
@@ -118,10 +171,11 @@ impl AppCmd {
         Ok(())
     }
 
-    async fn create_tenant(&self, id: &str, name: &str) -> Result<()> {
+    async fn create_tenant(&self, id: &str, name: &str, id_strategy: IdStrategy) -> Result<()> {
         let tenant = TenantPayload {
             id: id.to_string(),
             name: name.to_string(),
+            id_strategy: Some(id_strategy),
         };
         tenant.validate().map_err(|e| AppError::Validation(None, e.to_string()))?;
         let mut conn = self.state.get_conn().await?;
@@ -132,12 +186,103 @@ impl AppCmd {
         Ok(())
     }
 
+    async fn list_api_keys(&self, tid: Option<&str>) -> Result<()> {
+        let mut conn = self.state.get_conn().await?;
+        let mut tx = Connection::begin(&mut conn).await.map_err(AppError::DB)?;
+        let keys = ApiKey::find(&mut tx, tid).await?;
+        self.state.commit_tx(tx).await?;
+        for key in keys.iter() {
+            info!(
+                "{}: tenant={} scopes={} created_at={}",
+                key.id,
+                key.tid.as_deref().unwrap_or("*"),
+                key.scopes.join(","),
+                key.created_at,
+            );
+        }
+        Ok(())
+    }
+
+    async fn create_api_key(&self, tid: Option<&str>, scopes: &[Scope]) -> Result<()> {
+        let mut conn = self.state.get_conn().await?;
+        let mut tx = Connection::begin(&mut conn).await.map_err(AppError::DB)?;
+        let (key, token) = ApiKey::insert(&mut tx, tid, scopes).await?;
+        self.state.commit_tx(tx).await?;
+        info!("API key \"{}\" created, the token to use it is (it will not be displayed again):", key.id);
+        info!("{token}");
+        Ok(())
+    }
+
+    async fn revoke_api_key(&self, id: &str) -> Result<()> {
+        let mut conn = self.state.get_conn().await?;
+        let mut tx = Connection::begin(&mut conn).await.map_err(AppError::DB)?;
+        ApiKey::delete(&mut tx, id).await?;
+        self.state.commit_tx(tx).await?;
+        info!("API key \"{}\" revoked.", id);
+        Ok(())
+    }
+
+    async fn export_tenant(&self, id: &str) -> Result<()> {
+        let mut conn = self.state.get_conn().await?;
+        let tx = Connection::begin(&mut conn).await.map_err(AppError::DB)?;
+        let mut exporter = Exporter::begin(tx, id).await?;
+        let mut out = BufWriter::new(stdout().lock());
+        while let Some(chunk) = exporter.next_chunk().await? {
+            out.write_all(&chunk).map_err(|e| AppError::Unexpected(e.into()))?;
+        }
+        out.flush().map_err(|e| AppError::Unexpected(e.into()))?;
+        Ok(())
+    }
+
+    async fn import_tenant(&self, file: &Path, id: Option<&str>) -> Result<()> {
+        let reader = File::open(file)
+            .map(BufReader::new)
+            .map_err(|e| AppError::Unexpected(e.into()))?;
+        let mut conn = self.state.get_conn().await?;
+        let mut tx = Connection::begin(&mut conn).await.map_err(AppError::DB)?;
+        let mut importer = Importer::new(id);
+        for line in reader.split(b'\n') {
+            let line = line.map_err(|e| AppError::Unexpected(e.into()))?;
+            importer.push_line(&mut tx, &line).await?;
+        }
+        let result = importer.finish(&mut tx).await?;
+        self.state.commit_tx(tx).await?;
+        info!(
+            "Tenant \"{}\" imported with {} elements, {} sets and {} relationships.",
+            result.tenant.id, result.elements, result.sets, result.relationships
+        );
+        Ok(())
+    }
+
+    async fn purge(&self, older_than: TimeDelta) -> Result<()> {
+        let mut conn = self.state.get_conn().await?;
+        let mut tx = Connection::begin(&mut conn).await.map_err(AppError::DB)?;
+        let result = purge(&mut tx, older_than).await?;
+        self.state.commit_tx(tx).await?;
+        info!("Purged {} tenants and {} elements from the trash.", result.tenants, result.elements);
+        Ok(())
+    }
+
     fn list_envs(&self) {
         info!(
 r"# The following items are the environment variables and its values from
 # the OS, from an .env file, or the default value used by **Backset**.
 #");
         info!("{}", self.state.config.to_string());
+        info!("AUTH_MODE={}", env::var("AUTH_MODE").unwrap_or(AuthMode::default().to_string()));
+        for name in ["JWT_JWKS", "JWT_TENANT_CLAIM", "JWT_ISSUER", "JWT_AUDIENCE"] {
+            if let Ok(value) = env::var(name) {
+                info!("{name}={value}");
+            }
+        }
+        info!(
+            "SHUTDOWN_GRACE_PERIOD={}",
+            env::var("SHUTDOWN_GRACE_PERIOD").unwrap_or(DEFAULT_SHUTDOWN_GRACE_PERIOD.to_string())
+        );
+        info!(
+            "TRACE_EXPORTER={}",
+            env::var("TRACE_EXPORTER").unwrap_or(TraceExporter::default().to_string())
+        );
         info!("LOG_LEVEL={}", env::var("LOG_LEVEL").unwrap_or("INFO".to_string()));
         info!("RUST_LOG=\"{}\"", env::var("RUST_LOG").unwrap_or("".to_string()));
     }
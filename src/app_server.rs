@@ -3,25 +3,48 @@
 use actix_contrib_logger::middleware::Logger;
 use actix_contrib_rest::app_state::AppState;
 use actix_contrib_rest::response::json_error_handler;
-use actix_web::dev::Server;
-use actix_web::middleware::Compress;
+use actix_web::dev::{Server, ServerHandle};
+use actix_web::middleware::{from_fn, Compress, Condition};
 use actix_web::web;
 use actix_web::web::{Data, ServiceConfig};
 use actix_web::{App, HttpServer};
 use actix_web_validator::{JsonConfig, QueryConfig};
 use awc::http::StatusCode;
 use log::{error, info, Level};
+use opentelemetry_sdk::trace::SdkTracerProvider;
 use server_env_config::server::HttpServerConfig;
 use server_env_config::Config;
+use std::env;
 use std::process::exit;
+use std::time::Duration;
 
+use crate::auth::jwt::JwtAuth;
+use crate::auth::middleware::{api_key_auth, jwt_auth};
+use crate::auth::AuthMode;
+use crate::health::Readiness;
+use crate::metrics::metrics_middleware;
+use crate::telemetry::{self, request_tracing, TraceExporter};
 use crate::routes;
 
+/// Seconds the server keeps accepting requests after a SIGTERM, if
+/// the env variable `SHUTDOWN_GRACE_PERIOD` is not set.
+pub const DEFAULT_SHUTDOWN_GRACE_PERIOD: u64 = 5;
+
+/// Format of the access logs, the default format of the logger plus the request id.
+const LOG_FORMAT: &str = r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T %{x-request-id}o"#;
+
+/// Max seconds to wait for the requests in progress to
+/// complete once the server stops accepting requests.
+pub const SHUTDOWN_TIMEOUT: u64 = 30;
+
 /// Build and run the HTTP server.
 pub struct AppServer {
     pub server: Server,
     pub addr: String,
     pub port: u16,
+    /// Provider of the tracing spans, to be shut down once the server stops
+    /// with [`telemetry::shutdown()`].
+    pub tracer_provider: Option<SdkTracerProvider>,
 }
 
 impl AppServer {
@@ -30,18 +53,47 @@ impl AppServer {
     /// ```example
     /// let app = AppServer::build(config, "0.1.0").await?;
     /// app.server.await?;
+    /// telemetry::shutdown(app.tracer_provider);
     /// ```
     pub async fn build(config: Config, app_version: &str) -> Result<Self, anyhow::Error> {
         let HttpServerConfig { addr, port, uri, url } = config.server.clone();
         info!("🚀 Starting Backset server v{} at {} ...", app_version, url);
+        let auth_mode = AuthMode::from_env().unwrap_or_else(|error| {
+            error!("{error}");
+            exit(1);
+        });
+        info!("Authentication mode: {auth_mode}");
+        let trace_exporter = TraceExporter::from_env().unwrap_or_else(|error| {
+            error!("{error}");
+            exit(1);
+        });
+        info!("Trace exporter: {trace_exporter}");
+        let tracer_provider = telemetry::init(trace_exporter).unwrap_or_else(|error| {
+            error!("{error}");
+            exit(1);
+        });
+        let jwt_auth_data = match auth_mode {
+            AuthMode::Jwt => Some(Data::new(JwtAuth::from_env().await.unwrap_or_else(|error| {
+                error!("{error}");
+                exit(1);
+            }))),
+            _ => None,
+        };
         let state = AppState::init(config).await.unwrap_or_else(|error| {
             error!("{error}");
             exit(1);
         });
+        let grace_period = Self::shutdown_grace_period().unwrap_or_else(|error| {
+            error!("{error}");
+            exit(1);
+        });
+        let readiness = Data::new(Readiness::default());
+        let app_readiness = readiness.clone();
 
         let server = HttpServer::new(move || {
-            let config_app = Self::config_app(Data::new(state.clone()));
-            let logger = Logger::default()
+            let data = Data::new(state.clone());
+            let config_app = Self::config_app(data.clone());
+            let logger = Logger::new(LOG_FORMAT)
                 .custom_level(|status| {
                     if status.is_server_error() {
                         Level::Error
@@ -51,15 +103,67 @@ impl AppServer {
                         Level::Info
                     }
                 });
-            App::new()
+            // The state is also registered at the app level to be available to the auth middleware
+            let mut app = App::new().app_data(data).app_data(app_readiness.clone());
+            if let Some(jwt_auth_data) = jwt_auth_data.as_ref() {
+                app = app.app_data(jwt_auth_data.clone());
+            }
+            app
                 .service(web::scope(uri.as_str()).configure(config_app))
+                .wrap(Condition::new(auth_mode == AuthMode::ApiKey, from_fn(api_key_auth)))
+                .wrap(Condition::new(auth_mode == AuthMode::Jwt, from_fn(jwt_auth)))
+                .wrap(from_fn(metrics_middleware))
                 .wrap(Compress::default())
+                .wrap(from_fn(request_tracing))
                 .wrap(logger)
         })
         .bind((addr.clone(), port))?
+        // Signals are handled by the app to wait the grace period before stopping
+        .disable_signals()
+        .shutdown_timeout(SHUTDOWN_TIMEOUT)
         .run();
+        Self::handle_signals(server.handle(), readiness, grace_period);
+
+        Ok(AppServer { server, addr, port, tracer_provider })
+    }
+
+    /// Read the env variable `SHUTDOWN_GRACE_PERIOD`, in seconds.
+    fn shutdown_grace_period() -> core::result::Result<Duration, String> {
+        match env::var("SHUTDOWN_GRACE_PERIOD") {
+            Err(_) => Ok(Duration::from_secs(DEFAULT_SHUTDOWN_GRACE_PERIOD)),
+            Ok(secs) => secs.parse::<u64>()
+                .map(Duration::from_secs)
+                .map_err(|_| format!("invalid SHUTDOWN_GRACE_PERIOD \"{secs}\"")),
+        }
+    }
 
-        Ok(AppServer { server, addr, port })
+    /// Stop the server gracefully when a SIGINT (Ctrl+C) is received, or when
+    /// a SIGTERM is received once the grace period is over, time in which the
+    /// server keeps accepting requests but the readiness probe fails, so load
+    /// balancers like the ones of Kubernetes stop sending requests to the server.
+    fn handle_signals(server: ServerHandle, readiness: Data<Readiness>, grace_period: Duration) {
+        let handle = server.clone();
+        actix_web::rt::spawn(async move {
+            if actix_web::rt::signal::ctrl_c().await.is_ok() {
+                info!("SIGINT received, stopping server ...");
+                handle.stop(true).await;
+            }
+        });
+        #[cfg(unix)]
+        actix_web::rt::spawn(async move {
+            use actix_web::rt::signal::unix::{signal, SignalKind};
+            let Ok(mut sigterm) = signal(SignalKind::terminate()) else {
+                error!("Failed to listen for SIGTERM signals");
+                return;
+            };
+            sigterm.recv().await;
+            readiness.set_not_ready();
+            info!("SIGTERM received, stopping server in {}s ...", grace_period.as_secs());
+            actix_web::rt::time::sleep(grace_period).await;
+            server.stop(true).await;
+        });
+        #[cfg(not(unix))]
+        let _ = (readiness, grace_period);
     }
 
     /// Setup the app, receiving the state that will be passed
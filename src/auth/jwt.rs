@@ -0,0 +1,114 @@
+//! Authentication with JWT bearer tokens issued by a third party, verified
+//! with the public keys of a JWKS (JSON Web Key Set) document.
+
+use actix_contrib_rest::result::{AppError, Result};
+use awc::Client;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use log::info;
+use serde_json::{Map, Value};
+use std::env;
+use std::fs;
+use std::str::FromStr;
+
+use crate::auth::{Principal, Scope};
+
+/// Algorithms accepted to sign the tokens.
+const ALGORITHMS: [Algorithm; 2] = [Algorithm::RS256, Algorithm::ES256];
+
+/// Settings and keys to verify the tokens, configured with the env variables:
+/// - `JWT_JWKS`: path to a file or URL of the JWKS document with the public keys.
+/// - `JWT_TENANT_CLAIM`: claim with the tenant id, by default "tenant".
+/// - `JWT_ISSUER` and `JWT_AUDIENCE`: optional, if set the `iss` and
+///   `aud` claims of the tokens have to match them.
+///
+/// The scopes are taken from the standard `scope` claim, a list of values
+/// separated by spaces: "read", "write", and "admin" for tokens that
+/// have access to all the tenants.
+pub struct JwtAuth {
+    pub jwks: JwkSet,
+    pub tenant_claim: String,
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+}
+
+impl JwtAuth {
+    pub async fn from_env() -> core::result::Result<Self, String> {
+        let source = env::var("JWT_JWKS")
+            .map_err(|_| "env JWT_JWKS is required with AUTH_MODE=jwt".to_string())?;
+        info!("Loading JWKS from {source} ...");
+        let jwks = if source.starts_with("https://") || source.starts_with("http://") {
+            let mut res = Client::default()
+                .get(source.as_str())
+                .send()
+                .await
+                .map_err(|e| format!("error loading JWKS from \"{source}\": {e}"))?;
+            let body = res.body().await
+                .map_err(|e| format!("error loading JWKS from \"{source}\": {e}"))?;
+            String::from_utf8_lossy(&body).to_string()
+        } else {
+            fs::read_to_string(source.as_str())
+                .map_err(|e| format!("error loading JWKS from \"{source}\": {e}"))?
+        };
+        let mut auth = Self::from_jwks(jwks.as_str())?;
+        if let Ok(claim) = env::var("JWT_TENANT_CLAIM") {
+            auth.tenant_claim = claim;
+        }
+        auth.issuer = env::var("JWT_ISSUER").ok();
+        auth.audience = env::var("JWT_AUDIENCE").ok();
+        Ok(auth)
+    }
+
+    /// Create the settings with the JWKS document passed, and the default values.
+    pub fn from_jwks(jwks: &str) -> core::result::Result<Self, String> {
+        let jwks: JwkSet = serde_json::from_str(jwks)
+            .map_err(|e| format!("invalid JWKS: {e}"))?;
+        Ok(JwtAuth {
+            jwks,
+            tenant_claim: "tenant".to_string(),
+            issuer: None,
+            audience: None,
+        })
+    }
+
+    /// Verify the token, and get the principal from its claims.
+    pub fn decode(&self, token: &str) -> Result<Principal> {
+        let header = decode_header(token).map_err(|_| AppError::Unauthorized("invalid token"))?;
+        if !ALGORITHMS.contains(&header.alg) {
+            return Err(AppError::Unauthorized("token algorithm not supported"));
+        }
+        let jwk = match header.kid.as_deref() {
+            Some(kid) => self.jwks.find(kid),
+            // Without key id, only a set with one key can be used
+            None if self.jwks.keys.len() == 1 => self.jwks.keys.first(),
+            None => None,
+        }.ok_or(AppError::Unauthorized("token signed with an unknown key"))?;
+        let key = DecodingKey::from_jwk(jwk)
+            .map_err(|_| AppError::Unauthorized("token signed with an unknown key"))?;
+        let mut validation = Validation::new(header.alg);
+        match self.audience.as_deref() {
+            Some(aud) => validation.set_audience(&[aud]),
+            None => validation.validate_aud = false,
+        }
+        if let Some(iss) = self.issuer.as_deref() {
+            validation.set_issuer(&[iss]);
+        }
+        let claims = decode::<Map<String, Value>>(token, &key, &validation)
+            .map_err(|_| AppError::Unauthorized("invalid token"))?
+            .claims;
+        let scopes: Vec<&str> = claims.get("scope")
+            .and_then(Value::as_str)
+            .map(|s| s.split_whitespace().collect())
+            .unwrap_or_default();
+        let tid = match claims.get(self.tenant_claim.as_str()).and_then(Value::as_str) {
+            _ if scopes.contains(&"admin") => None,
+            Some(tid) => Some(tid.to_string()),
+            None => return Err(AppError::Unauthorized("token without tenant")),
+        };
+        Ok(Principal {
+            id: claims.get("sub").and_then(Value::as_str).unwrap_or("").to_string(),
+            tid,
+            scopes: scopes.iter().filter_map(|s| Scope::from_str(s).ok()).collect(),
+        })
+    }
+}
@@ -0,0 +1,155 @@
+use actix_contrib_rest::app_state::AppState;
+use actix_contrib_rest::result::{AppError, Result};
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::web::Data;
+use actix_web::{Error, HttpMessage};
+
+use crate::auth::jwt::JwtAuth;
+use crate::auth::model::ApiKey;
+use crate::auth::{Principal, Scope};
+
+/// What a request path gives access to.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PathTarget<'a> {
+    /// Endpoints that don't require authentication, like "/health".
+    Public,
+    /// The tenants administration endpoints, "/tenants...", and "/metrics".
+    Admin,
+    /// Endpoints of a tenant, "/{tenant}...".
+    Tenant(&'a str),
+}
+
+/// Get what the path of a request gives access to, where `uri`
+/// is the prefix of all the endpoints (env `APP_URI`).
+///
+/// ```
+/// use backset::auth::middleware::{path_target, PathTarget};
+/// assert_eq!(path_target("/health", ""), PathTarget::Public);
+/// assert_eq!(path_target("/tenants/t1", ""), PathTarget::Admin);
+/// assert_eq!(path_target("/metrics", ""), PathTarget::Admin);
+/// assert_eq!(path_target("/api/t1/sets", "api"), PathTarget::Tenant("t1"));
+/// ```
+pub fn path_target<'a>(path: &'a str, uri: &str) -> PathTarget<'a> {
+    match relative_path(path, uri).split('/').next().unwrap_or("") {
+        "health" => PathTarget::Public,
+        "" | "tenants" | "metrics" => PathTarget::Admin,
+        tid => PathTarget::Tenant(tid),
+    }
+}
+
+/// The path without the leading `uri` prefix and slash.
+fn relative_path<'a>(path: &'a str, uri: &str) -> &'a str {
+    let path = path.trim_start_matches('/');
+    match uri.trim_matches('/') {
+        "" => path,
+        uri => path.strip_prefix(uri).unwrap_or(path).trim_start_matches('/'),
+    }
+}
+
+/// Endpoints of the tenants requested with `POST` that only read data,
+/// as the path after the tenant, e.g. "_mget" for "/{tenant}/_mget".
+const READ_ONLY_POSTS: [&str; 1] = ["_mget"];
+
+/// Get the scope required by a request, the one of its method,
+/// except for the `POST` endpoints that only read data.
+///
+/// ```
+/// use actix_web::http::Method;
+/// use backset::auth::Scope;
+/// use backset::auth::middleware::required_scope;
+/// assert_eq!(required_scope(&Method::GET, "/t1/e1", ""), Scope::Read);
+/// assert_eq!(required_scope(&Method::POST, "/t1", ""), Scope::Write);
+/// assert_eq!(required_scope(&Method::POST, "/t1/_mget", ""), Scope::Read);
+/// assert_eq!(required_scope(&Method::POST, "/api/t1/_mget", "api"), Scope::Read);
+/// assert_eq!(required_scope(&Method::POST, "/tenants/_mget", ""), Scope::Write);
+/// ```
+pub fn required_scope(method: &Method, path: &str, uri: &str) -> Scope {
+    if *method == Method::POST
+        && let PathTarget::Tenant(_) = path_target(path, uri)
+        && let Some((_, endpoint)) = relative_path(path, uri).split_once('/')
+        && READ_ONLY_POSTS.contains(&endpoint.trim_end_matches('/')) {
+        return Scope::Read;
+    }
+    Scope::of_method(method)
+}
+
+/// Get the bearer token from the `Authorization` header.
+pub fn bearer_token(req: &ServiceRequest) -> Option<&str> {
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Authorize the principal with the path and method of the request, making
+/// it available to the handlers through the request extensions.
+pub fn authorize(req: &ServiceRequest, principal: Principal, uri: &str) -> Result<()> {
+    let scope = required_scope(req.method(), req.path(), uri);
+    match path_target(req.path(), uri) {
+        PathTarget::Public => {}
+        PathTarget::Admin => principal.authorize(None, scope)?,
+        PathTarget::Tenant(tid) => principal.authorize(Some(tid), scope)?,
+    }
+    req.extensions_mut().insert(principal);
+    Ok(())
+}
+
+fn app_data<T: 'static>(req: &ServiceRequest) -> Result<&Data<T>> {
+    req.app_data::<Data<T>>().ok_or(AppError::Unexpected(anyhow::anyhow!(
+        "{} not configured", std::any::type_name::<T>()
+    )))
+}
+
+async fn authenticate_api_key(req: &ServiceRequest) -> Result<()> {
+    let app = app_data::<AppState>(req)?;
+    let uri = app.config.server.uri.as_str();
+    if path_target(req.path(), uri) == PathTarget::Public {
+        return Ok(());
+    }
+    let token = bearer_token(req).ok_or(AppError::Unauthorized("API key required"))?;
+    let mut tx = app.get_tx().await?;
+    let key = ApiKey::find_by_token(&mut tx, token).await?;
+    app.commit_tx(tx).await?;
+    let key = key.ok_or(AppError::Unauthorized("invalid API key"))?;
+    authorize(req, key.principal(), uri)
+}
+
+/// Middleware that authenticates the requests with the API keys passed as bearer
+/// tokens. The app state has to be registered at the app level with `App::app_data()`.
+pub async fn api_key_auth(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> core::result::Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    match authenticate_api_key(&req).await {
+        Ok(()) => next.call(req).await.map(ServiceResponse::map_into_left_body),
+        Err(e) => Ok(req.error_response(e).map_into_right_body()),
+    }
+}
+
+fn authenticate_jwt(req: &ServiceRequest) -> Result<()> {
+    let app = app_data::<AppState>(req)?;
+    let jwt = app_data::<JwtAuth>(req)?;
+    let uri = app.config.server.uri.as_str();
+    if path_target(req.path(), uri) == PathTarget::Public {
+        return Ok(());
+    }
+    let token = bearer_token(req).ok_or(AppError::Unauthorized("token required"))?;
+    authorize(req, jwt.decode(token)?, uri)
+}
+
+/// Middleware that authenticates the requests with JWT bearer tokens. The app state
+/// and the [`JwtAuth`] settings have to be registered at the app level with `App::app_data()`.
+pub async fn jwt_auth(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> core::result::Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    match authenticate_jwt(&req) {
+        Ok(()) => next.call(req).await.map(ServiceResponse::map_into_left_body),
+        Err(e) => Ok(req.error_response(e).map_into_right_body()),
+    }
+}
@@ -0,0 +1,108 @@
+//! Authentication and authorization of the requests.
+//!
+//! The mode is set with the env variable `AUTH_MODE`, by default `none`,
+//! where all the endpoints are public.
+
+use actix_contrib_rest::result::{AppError, Result};
+use actix_web::http::Method;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::str::FromStr;
+
+pub mod jwt;
+pub mod middleware;
+pub mod model;
+
+/// How the requests are authenticated.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, strum_macros::Display, strum_macros::EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum AuthMode {
+    /// All the endpoints are public.
+    #[default]
+    None,
+    /// Requests need a bearer API key, see [`model::ApiKey`].
+    ApiKey,
+    /// Requests need a bearer JWT token, see [`jwt::JwtAuth`].
+    Jwt,
+}
+
+impl AuthMode {
+    /// Read the mode from the env variable `AUTH_MODE`.
+    pub fn from_env() -> core::result::Result<Self, String> {
+        match env::var("AUTH_MODE") {
+            Err(_) => Ok(AuthMode::default()),
+            Ok(mode) => AuthMode::from_str(mode.as_str())
+                .map_err(|_| format!("invalid AUTH_MODE \"{mode}\"")),
+        }
+    }
+}
+
+/// Permissions granted to a principal over its tenant.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize,
+    strum_macros::Display, strum_macros::EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Scope {
+    /// Read-only requests (`GET` and `HEAD`, and `POST /{tenant}/_mget`).
+    Read,
+    /// Requests that can modify data (`POST`, `PUT`, `PATCH` and `DELETE`).
+    Write,
+}
+
+impl Scope {
+    /// Scope required by the request method.
+    pub fn of_method(method: &Method) -> Self {
+        match *method {
+            Method::GET | Method::HEAD | Method::OPTIONS => Scope::Read,
+            _ => Scope::Write,
+        }
+    }
+}
+
+/// The authenticated client of a request, available to the
+/// handlers through the request extensions, e.g.
+/// `req.extensions().get::<Principal>()`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Principal {
+    /// Identifier of the client, e.g. the API key id.
+    pub id: String,
+    /// Tenant the principal has access to, `None` for admins
+    /// that have access to all the tenants.
+    pub tid: Option<String>,
+    pub scopes: Vec<Scope>,
+}
+
+impl Principal {
+    pub fn is_admin(&self) -> bool {
+        self.tid.is_none()
+    }
+
+    /// Check the principal can access the tenant `tid` (or the tenants
+    /// administration endpoints if `None`) with the scope passed.
+    ///
+    /// ```
+    /// use backset::auth::{Principal, Scope};
+    /// let p = Principal { id: "k1".into(), tid: Some("t1".into()), scopes: vec![Scope::Read] };
+    /// assert!(p.authorize(Some("t1"), Scope::Read).is_ok());
+    /// assert!(p.authorize(Some("t1"), Scope::Write).is_err());
+    /// assert!(p.authorize(Some("t2"), Scope::Read).is_err());
+    /// assert!(p.authorize(None, Scope::Read).is_err());
+    /// ```
+    pub fn authorize(&self, tid: Option<&str>, scope: Scope) -> Result<()> {
+        if !self.is_admin() && self.tid.as_deref() != tid {
+            return Err(match tid {
+                None => AppError::Forbidden("admin access required"),
+                Some(_) => AppError::Forbidden("access to the tenant not allowed"),
+            });
+        }
+        if !self.scopes.contains(&scope) {
+            return Err(match scope {
+                Scope::Read => AppError::Forbidden("\"read\" scope required"),
+                Scope::Write => AppError::Forbidden("\"write\" scope required"),
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+use actix_contrib_rest::db::Tx;
+use actix_contrib_rest::result::{AppError, Result};
+use chrono::NaiveDateTime;
+use rand::random;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+use tracing::instrument;
+
+use crate::auth::{Principal, Scope};
+use crate::tenants::model::Tenant;
+
+/// API keys are passed as bearer tokens with the format `{id}.{secret}`,
+/// only the SHA-256 hash of the secret is stored.
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct ApiKey {
+    pub id: String,
+    /// Tenant the key has access to, `None` for admin keys.
+    pub tid: Option<String>,
+    #[serde(skip_serializing, default)]
+    pub secret_hash: String,
+    pub scopes: Vec<String>,
+    pub created_at: NaiveDateTime,
+}
+
+fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+/// Compare in constant time, so the time taken doesn't leak
+/// how many characters match.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+impl ApiKey {
+    /// Create a new key, returning the key and the token to
+    /// use it, that cannot be retrieved later.
+    #[instrument(name = "ApiKey::insert", skip_all, fields(tid = ?tid))]
+    pub async fn insert(
+        tx: &mut Tx<'_>,
+        tid: Option<&str>,
+        scopes: &[Scope],
+    ) -> Result<(ApiKey, String)> {
+        if let Some(tid) = tid {
+            Tenant::exists_or_fail(tx, tid).await?;
+        }
+        if scopes.is_empty() {
+            return Err(AppError::StaticValidation("at least one scope is required"));
+        }
+        let id = format!("{:016x}", random::<u64>());
+        let secret: String = random::<[u8; 32]>().iter().map(|b| format!("{b:02x}")).collect();
+        let scopes: Vec<String> = scopes.iter().map(|s| s.to_string()).collect();
+        let key = sqlx::query_as::<_, ApiKey>(
+                r#"
+            INSERT INTO api_keys (id, tid, secret_hash, scopes, created_at)
+              VALUES ($1, $2, $3, $4, NOW())
+              RETURNING *
+                "#
+            )
+            .bind(id.as_str())
+            .bind(tid)
+            .bind(hash_secret(secret.as_str()))
+            .bind(&scopes)
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok((key, format!("{id}.{secret}")))
+    }
+
+    /// Get the key of the token passed, as long as the secret is valid.
+    #[instrument(name = "ApiKey::find_by_token", skip_all)]
+    pub async fn find_by_token(tx: &mut Tx<'_>, token: &str) -> Result<Option<ApiKey>> {
+        let Some((id, secret)) = token.split_once('.') else {
+            return Ok(None);
+        };
+        let key: Option<ApiKey> = sqlx::query_as("SELECT * FROM api_keys WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(key.filter(|k| constant_time_eq(k.secret_hash.as_str(), hash_secret(secret).as_str())))
+    }
+
+    /// Get all the keys, or only the keys of the tenant `tid` if passed.
+    #[instrument(name = "ApiKey::find", skip_all, fields(tid = ?tid))]
+    pub async fn find(tx: &mut Tx<'_>, tid: Option<&str>) -> Result<Vec<ApiKey>> {
+        sqlx::query_as(
+                r#"
+            SELECT * FROM api_keys
+              WHERE $1::VARCHAR IS NULL OR tid = $1
+              ORDER BY created_at, id
+                "#
+            )
+            .bind(tid)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)
+    }
+
+    /// Revoke the key, so it cannot be used anymore.
+    #[instrument(name = "ApiKey::delete", skip_all)]
+    pub async fn delete(tx: &mut Tx<'_>, id: &str) -> Result<()> {
+        let res = sqlx::query("DELETE FROM api_keys WHERE id = $1")
+            .bind(id)
+            .execute(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        if res.rows_affected() == 0 {
+            return Err(AppError::ResourceNotFound {
+                resource: "api key",
+                attribute: "id",
+                value: id.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn principal(&self) -> Principal {
+        Principal {
+            id: self.id.clone(),
+            tid: self.tid.clone(),
+            scopes: self.scopes.iter().filter_map(|s| Scope::from_str(s).ok()).collect(),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Conditional requests (RFC 9110), with the version of the
+//! resources as `ETag`, and the headers `If-Match` and `If-None-Match`.
+
+use actix_contrib_rest::result::ValidationErrorPayload;
+use actix_web::dev::Payload;
+use actix_web::http::header::{EntityTag, ETag, Header, IfMatch, IfNoneMatch};
+use actix_web::http::Method;
+use actix_web::{FromRequest, HttpRequest, HttpResponse};
+use std::future::{ready, Ready};
+
+/// Entity tag of the version of a resource.
+///
+/// ```
+/// use backset::conditional::etag;
+/// assert_eq!(etag(3).to_string(), "\"3\"");
+/// ```
+pub fn etag(version: i64) -> ETag {
+    ETag(EntityTag::new_strong(version.to_string()))
+}
+
+/// Preconditions of a request, extracted from its headers.
+#[derive(Debug, Default)]
+pub struct Preconditions {
+    pub if_match: Option<IfMatch>,
+    pub if_none_match: Option<IfNoneMatch>,
+    /// Whether the request is a `GET` or `HEAD` request.
+    pub safe: bool,
+}
+
+impl Preconditions {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        // Headers not valid are taken as a list of tags that don't match any version
+        let if_match = req.headers().contains_key(IfMatch::name())
+            .then(|| IfMatch::parse(req).unwrap_or(IfMatch::Items(Vec::new())));
+        let if_none_match = req.headers().contains_key(IfNoneMatch::name())
+            .then(|| IfNoneMatch::parse(req).unwrap_or(IfNoneMatch::Items(Vec::new())));
+        let safe = req.method() == Method::GET || req.method() == Method::HEAD;
+        Preconditions { if_match, if_none_match, safe }
+    }
+
+    /// Whether the request has no preconditions.
+    pub fn is_empty(&self) -> bool {
+        self.if_match.is_none() && self.if_none_match.is_none()
+    }
+
+    /// Evaluate the preconditions with the current version of the resource, or `None`
+    /// if it doesn't exist. If they fail, the response to send instead of processing
+    /// the request is returned: "304 Not Modified" for `GET` and `HEAD` requests
+    /// with a tag matched by `If-None-Match`, and "412 Precondition Failed" otherwise.
+    pub fn evaluate(&self, version: Option<i64>) -> Option<HttpResponse> {
+        let current = version.map(|v| etag(v).0);
+        let if_match = match (&self.if_match, &current) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(IfMatch::Any), Some(_)) => true,
+            (Some(IfMatch::Items(tags)), Some(current)) => tags.iter().any(|t| t.strong_eq(current)),
+        };
+        if !if_match {
+            return Some(precondition_failed());
+        }
+        let if_none_match = match (&self.if_none_match, &current) {
+            (None, _) | (Some(_), None) => true,
+            (Some(IfNoneMatch::Any), Some(_)) => false,
+            (Some(IfNoneMatch::Items(tags)), Some(current)) => !tags.iter().any(|t| t.weak_eq(current)),
+        };
+        match (if_none_match, self.safe, version) {
+            (true, _, _) => None,
+            (false, true, Some(version)) => Some(
+                HttpResponse::NotModified().insert_header(etag(version)).finish()
+            ),
+            (false, _, _) => Some(precondition_failed()),
+        }
+    }
+}
+
+impl FromRequest for Preconditions {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(Preconditions::from_request(req)))
+    }
+}
+
+fn precondition_failed() -> HttpResponse {
+    HttpResponse::PreconditionFailed().json(ValidationErrorPayload::with_code(
+        "precondition_failed".to_string(),
+        "the resource doesn't match the version expected".to_string(),
+    ))
+}
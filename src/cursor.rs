@@ -0,0 +1,99 @@
+//! Cursor (keyset) pagination, an alternative to paginate with `offset`
+//! that is stable when records are inserted concurrently, and doesn't
+//! degrade with large collections.
+//!
+//! The cursor is an opaque token passed with the `after` argument, and
+//! obtained from the `next` field of the previous page.
+
+use actix_contrib_rest::page::Page;
+use actix_contrib_rest::query::QuerySearch;
+use actix_contrib_rest::result::{AppError, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CursorQuery {
+    #[validate(length(min = 1, max = 1024))]
+    pub after: Option<String>,
+}
+
+impl CursorQuery {
+    /// Decode the `after` token if present, failing if the token is
+    /// invalid or combined with the `sort` or `offset` arguments.
+    pub fn decode<C: DeserializeOwned>(&self, query: &QuerySearch) -> Result<Option<C>> {
+        let Some(after) = self.after.as_deref() else {
+            return Ok(None);
+        };
+        if query.sort.is_some() {
+            return Err(AppError::StaticValidation(
+                "argument \"after\" cannot be used with \"sort\""
+            ));
+        }
+        if query.offset != 0 {
+            return Err(AppError::StaticValidation(
+                "argument \"after\" cannot be used with \"offset\""
+            ));
+        }
+        decode(after).map(Some)
+    }
+}
+
+/// A page of results with the cursor to get the next page.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CursorPage<T> {
+    #[serde(flatten)]
+    pub page: Page<T>,
+    /// Token to pass with the `after` argument to get the next page,
+    /// only present if there may be more results.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+}
+
+impl<T> CursorPage<T> {
+    /// Create the page, with the `next` cursor built with the keys of the last record
+    /// returned by `key`, as long as the page is full and the records are sorted
+    /// in the default order, the only one supported by cursors.
+    pub fn new<C: Serialize>(page: Page<T>, query: &QuerySearch, key: impl Fn(&T) -> C) -> Self {
+        let next = match page.data.last() {
+            Some(last) if query.sort.is_none() && page.page_size >= query.page_size => {
+                Some(encode(&key(last)))
+            }
+            _ => None,
+        };
+        CursorPage { page, next }
+    }
+}
+
+impl<T> From<Page<T>> for CursorPage<T> {
+    /// Creates a page without the `next` cursor.
+    fn from(page: Page<T>) -> Self {
+        CursorPage { page, next: None }
+    }
+}
+
+/// Encode the keys of a record into an opaque token.
+///
+/// ```
+/// use backset::cursor::{decode, encode};
+/// let token = encode(&("a", 1));
+/// assert_eq!(decode::<(String, i32)>(&token).unwrap(), ("a".to_string(), 1));
+/// assert!(decode::<(String, i32)>("not-a-cursor").is_err());
+/// ```
+pub fn encode<C: Serialize>(keys: &C) -> String {
+    // Serializing a tuple or a scalar into JSON cannot fail
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(keys).unwrap_or_default())
+}
+
+/// Decode a token created with [`encode()`].
+pub fn decode<C: DeserializeOwned>(token: &str) -> Result<C> {
+    URL_SAFE_NO_PAD.decode(token)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or_else(|| AppError::Validation(
+            Some("invalid_cursor"),
+            format!("invalid cursor \"{token}\""),
+        ))
+}
@@ -1,41 +1,114 @@
 use actix_contrib_rest::app_state::AppState;
 use actix_contrib_rest::page::Page;
-use actix_contrib_rest::query::QuerySearch;
-use actix_contrib_rest::result::HttpResult;
-use actix_web::web::{Data, Path};
-use actix_web::{delete, get, post, put, HttpResponse};
+use actix_contrib_rest::query::{Force, QuerySearch};
+use actix_contrib_rest::result::{AppError, HttpResult, ValidationErrorPayload};
+use actix_web::web;
+use actix_web::web::{Bytes, Data, Path, ReqData};
+use actix_web::{delete, get, patch, post, put, HttpMessage, HttpRequest, HttpResponse};
 use actix_web_validator::{Json, Query};
 
-use crate::elements::model::{Element, ElementPayload};
+use crate::auth::Principal;
+use crate::conditional::{etag, Preconditions};
+use crate::cursor::{CursorPage, CursorQuery};
+use crate::elements::bulk::{execute, parse_items, BulkMode, BulkQuery, NDJSON_CONTENT_TYPE};
+use crate::elements::filter::ElementFilter;
+use crate::elements::model::{
+    Element, ElementCursor, ElementIdsPayload, ElementPatch, ElementPayload,
+    JSON_PATCH_CONTENT_TYPE, MERGE_PATCH_CONTENT_TYPE,
+};
+use crate::elements::revisions::{AtQuery, ElementRevision};
+use crate::metrics::METRICS;
 use crate::tenants::model::Tenant;
+use crate::trash::{IncludeDeletedQuery, SoftDeleteQuery};
 
 #[post("{tid}")]
 async fn create(
     app: Data<AppState>,
     tid: Path<String>,
     el_form: Json<ElementPayload>,
+    principal: Option<ReqData<Principal>>,
 ) -> HttpResult {
+    let by = principal.as_deref().map(|p| p.id.as_str());
     let mut tx = app.get_tx().await?;
 
-    let el = Element::insert(&mut tx, tid.as_str(), el_form.0).await?;
+    let el = Element::insert(&mut tx, tid.as_str(), el_form.0, by).await?;
 
     app.commit_tx(tx).await?;
-    Ok(HttpResponse::Created().json(el))
+    METRICS.elements_created.with_label_values(&[tid.as_str()]).inc();
+    Ok(HttpResponse::Created().insert_header(etag(el.version)).json(el))
+}
+
+/// `POST /{tenant}/_bulk`, registered by hand in the routes
+/// to raise the body limit only for it, up to [`MAX_BULK_SIZE`].
+///
+/// [`MAX_BULK_SIZE`]: crate::elements::bulk::MAX_BULK_SIZE
+pub async fn bulk(
+    app: Data<AppState>,
+    tid: Path<String>,
+    query: Query<BulkQuery>,
+    req: HttpRequest,
+    body: Bytes,
+    principal: Option<ReqData<Principal>>,
+) -> HttpResult {
+    let by = principal.as_deref().map(|p| p.id.as_str());
+    let items = parse_items(&body, req.content_type() == NDJSON_CONTENT_TYPE)?;
+    let mut tx = app.get_tx().await?;
+    Tenant::exists_or_fail(&mut tx, tid.as_str()).await?;
+
+    let result = execute(&mut tx, tid.as_str(), items, query.mode, by).await?;
+
+    if result.errors && query.mode == BulkMode::Atomic {
+        app.rollback_tx(tx).await?;
+    } else {
+        app.commit_tx(tx).await?;
+        for item in result.items.iter() {
+            match (item.action.as_deref(), item.status) {
+                (Some("create"), 201) => METRICS.elements_created.with_label_values(&[tid.as_str()]).inc(),
+                (Some("delete"), 204) => METRICS.elements_deleted.with_label_values(&[tid.as_str()]).inc(),
+                _ => {}
+            }
+        }
+    }
+    Ok(HttpResponse::Ok().json(result))
+}
+
+#[post("{tid}/_mget")]
+async fn mget(
+    app: Data<AppState>,
+    tid: Path<String>,
+    ids_form: Json<ElementIdsPayload>,
+) -> HttpResult {
+    let mut tx = app.get_tx().await?;
+
+    let found = Element::get_many(&mut tx, tid.as_str(), &ids_form.ids).await?;
+
+    app.commit_tx(tx).await?;
+    Ok(HttpResponse::Ok().json(found))
 }
 
 #[get("{tid}/{id}")]
-async fn read(app: Data<AppState>, path: Path<(String, String)>) -> HttpResult {
+async fn read(
+    app: Data<AppState>,
+    path: Path<(String, String)>,
+    at_query: Query<AtQuery>,
+    deleted_query: Query<IncludeDeletedQuery>,
+    preconditions: Preconditions,
+) -> HttpResult {
+    let (tid, id) = path.into_inner();
+    let include_deleted = deleted_query.include_deleted.unwrap_or(false);
     let mut tx = app.get_tx().await?;
 
-    let element = Element::get(
-        &mut tx,
-        path.as_ref().0.as_str(),
-        path.as_ref().1.as_str()
-    ).await?;
+    let element = match at_query.time() {
+        Some(at) => ElementRevision::element_at(&mut tx, tid.as_str(), id.as_str(), at).await?,
+        None => Element::get(&mut tx, tid.as_str(), id.as_str(), include_deleted).await?,
+    };
 
     app.commit_tx(tx).await?;
     match element {
-        Some(el) => Ok(HttpResponse::Ok().json(el)),
+        Some(el) => match preconditions.evaluate(Some(el.version)) {
+            Some(res) => Ok(res),
+            None => Ok(HttpResponse::Ok().insert_header(etag(el.version)).json(el)),
+        },
         None => Ok(HttpResponse::NotFound().finish()),
     }
 }
@@ -44,23 +117,37 @@ async fn read(app: Data<AppState>, path: Path<(String, String)>) -> HttpResult {
 async fn list(
     app: Data<AppState>,
     tid: Path<String>,
-    query: Query<QuerySearch>
+    query: Query<QuerySearch>,
+    cursor: Query<CursorQuery>,
+    args: web::Query<Vec<(String, String)>>,
 ) -> HttpResult {
+    let filter = ElementFilter::parse(&args)?;
+    let query = query.into_inner();
+    let after: Option<ElementCursor> = cursor.decode(&query)?;
+    if after.is_some() && filter.search.is_some() {
+        return Err(AppError::StaticValidation("argument \"after\" cannot be used with \"q\""));
+    }
     let mut tx = app.get_tx().await?;
     Tenant::exists_or_fail(&mut tx, tid.as_str()).await?;
-    let query = query.into_inner();
     let total = if query.include_total.unwrap_or(true) {
-        Some(Element::count(&mut tx, tid.as_str()).await?)
+        Some(Element::count(&mut tx, tid.as_str(), &filter).await?)
     } else {
         None
     };
     let elements = match total {
         Some(0) => Page::empty(),
         _ => {
-            let data = Element::find(&mut tx, tid.as_str(), &query).await?;
+            let data = Element::find(
+                &mut tx, tid.as_str(), &query, &filter, after.as_ref()
+            ).await?;
             Page::with_data(data, total, query.offset)
         }
     };
+    let elements = match filter.search {
+        // Results are sorted by relevance, not supported by cursors
+        Some(_) => CursorPage::from(elements),
+        None => CursorPage::new(elements, &query, |e| (e.created_at, e.id.clone())),
+    };
     app.commit_tx(tx).await?;
     Ok(HttpResponse::Ok().json(elements))
 }
@@ -70,33 +157,179 @@ async fn put(
     app: Data<AppState>,
     path: Path<(String, String)>,
     el_form: Json<ElementPayload>,
+    preconditions: Preconditions,
+    principal: Option<ReqData<Principal>>,
 ) -> HttpResult {
+    let by = principal.as_deref().map(|p| p.id.as_str());
     let mut tx = app.get_tx().await?;
+    if !preconditions.is_empty() {
+        let version = Element::lock_version(
+            &mut tx, path.as_ref().0.as_str(), path.as_ref().1.as_str()
+        ).await?;
+        if let Some(res) = preconditions.evaluate(version) {
+            return Ok(res);
+        }
+    }
 
     let el = Element::save(
         &mut tx,
         path.as_ref().0.as_str(),
         path.as_ref().1.as_str(),
-        el_form.0
+        el_form.0,
+        by,
     ).await?;
 
     app.commit_tx(tx).await?;
-    Ok(HttpResponse::Ok().json(el))
+    Ok(HttpResponse::Ok().insert_header(etag(el.version)).json(el))
 }
 
-#[delete("{tid}/{id}")]
-async fn delete(app: Data<AppState>, path: Path<(String, String)>) -> HttpResult {
+#[patch("{tid}/{id}")]
+async fn patch(
+    app: Data<AppState>,
+    path: Path<(String, String)>,
+    req: HttpRequest,
+    body: Bytes,
+    preconditions: Preconditions,
+    principal: Option<ReqData<Principal>>,
+) -> HttpResult {
+    let by = principal.as_deref().map(|p| p.id.as_str());
+    let Some(patch) = ElementPatch::parse(req.content_type(), &body) else {
+        return Ok(HttpResponse::UnsupportedMediaType().json(ValidationErrorPayload::with_code(
+            "unsupported_media_type".to_string(),
+            format!("content type has to be \"{MERGE_PATCH_CONTENT_TYPE}\" or \"{JSON_PATCH_CONTENT_TYPE}\""),
+        )));
+    };
+    let patch = patch?;
     let mut tx = app.get_tx().await?;
+    if !preconditions.is_empty() {
+        let version = Element::lock_version(
+            &mut tx, path.as_ref().0.as_str(), path.as_ref().1.as_str()
+        ).await?;
+        if let Some(res) = preconditions.evaluate(version) {
+            return Ok(res);
+        }
+    }
 
-    let rows_deleted = Element::delete(
+    let element = Element::patch(
         &mut tx,
         path.as_ref().0.as_str(),
-        path.as_ref().1.as_str()
+        path.as_ref().1.as_str(),
+        &patch,
+        by,
     ).await?;
 
+    app.commit_tx(tx).await?;
+    match element {
+        Some(el) => Ok(HttpResponse::Ok().insert_header(etag(el.version)).json(el)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[delete("{tid}/{id}")]
+async fn delete(
+    app: Data<AppState>,
+    path: Path<(String, String)>,
+    query: Query<Force>,
+    soft_query: Query<SoftDeleteQuery>,
+    preconditions: Preconditions,
+) -> HttpResult {
+    let soft = soft_query.soft.unwrap_or(false);
+    if soft && query.force.is_some() {
+        return Err(AppError::StaticValidation("argument \"force\" cannot be used with \"soft\""));
+    }
+    let mut tx = app.get_tx().await?;
+    if !preconditions.is_empty() {
+        let version = Element::lock_version(
+            &mut tx, path.as_ref().0.as_str(), path.as_ref().1.as_str()
+        ).await?;
+        if let Some(res) = preconditions.evaluate(version) {
+            return Ok(res);
+        }
+    }
+
+    let rows_deleted = if soft {
+        Element::soft_delete(&mut tx, path.as_ref().0.as_str(), path.as_ref().1.as_str()).await?
+    } else {
+        Element::delete(
+            &mut tx,
+            path.as_ref().0.as_str(),
+            path.as_ref().1.as_str(),
+            query.force.unwrap_or(false),
+        ).await?
+    };
+
     app.commit_tx(tx).await?;
     match rows_deleted {
         0 => Ok(HttpResponse::NotFound().finish()),
-        _ => Ok(HttpResponse::NoContent().finish()),
+        _ => {
+            METRICS.elements_deleted.with_label_values(&[path.as_ref().0.as_str()]).inc();
+            Ok(HttpResponse::NoContent().finish())
+        }
+    }
+}
+
+#[post("{tid}/{id}/restore")]
+async fn restore(app: Data<AppState>, path: Path<(String, String)>) -> HttpResult {
+    let (tid, id) = path.into_inner();
+    let mut tx = app.get_tx().await?;
+
+    let element = Element::restore(&mut tx, tid.as_str(), id.as_str()).await?;
+
+    app.commit_tx(tx).await?;
+    match element {
+        Some(el) => Ok(HttpResponse::Ok().insert_header(etag(el.version)).json(el)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[get("{tid}/{id}/revisions")]
+async fn revisions(
+    app: Data<AppState>,
+    path: Path<(String, String)>,
+    query: Query<QuerySearch>,
+) -> HttpResult {
+    let (tid, id) = path.into_inner();
+    let mut tx = app.get_tx().await?;
+    Tenant::exists_or_fail(&mut tx, tid.as_str()).await?;
+    let query = query.into_inner();
+    let total = if query.include_total.unwrap_or(true) {
+        Some(ElementRevision::count(&mut tx, tid.as_str(), id.as_str()).await?)
+    } else {
+        None
+    };
+    let revisions = match total {
+        Some(0) => Page::empty(),
+        _ => {
+            let data = ElementRevision::find(&mut tx, tid.as_str(), id.as_str(), &query).await?;
+            Page::with_data(data, total, query.offset)
+        }
+    };
+    app.commit_tx(tx).await?;
+    Ok(HttpResponse::Ok().json(revisions))
+}
+
+#[post("{tid}/{id}/revisions/{version}/restore")]
+async fn restore_revision(
+    app: Data<AppState>,
+    path: Path<(String, String, i64)>,
+    preconditions: Preconditions,
+    principal: Option<ReqData<Principal>>,
+) -> HttpResult {
+    let by = principal.as_deref().map(|p| p.id.as_str());
+    let (tid, id, version) = path.into_inner();
+    let mut tx = app.get_tx().await?;
+    if !preconditions.is_empty() {
+        let current = Element::lock_version(&mut tx, tid.as_str(), id.as_str()).await?;
+        if let Some(res) = preconditions.evaluate(current) {
+            return Ok(res);
+        }
+    }
+
+    let element = ElementRevision::restore(&mut tx, tid.as_str(), id.as_str(), version, by).await?;
+
+    app.commit_tx(tx).await?;
+    match element {
+        Some(el) => Ok(HttpResponse::Ok().insert_header(etag(el.version)).json(el)),
+        None => Ok(HttpResponse::NotFound().finish()),
     }
 }
@@ -0,0 +1,242 @@
+//! Bulk operations over the elements of a tenant, see `POST /{tenant}/_bulk`.
+
+use actix_contrib_rest::db::Tx;
+use actix_contrib_rest::result::{AppError, Result, ValidationErrorPayload};
+use actix_web::body::to_bytes;
+use actix_web::http::StatusCode;
+use actix_web::ResponseError;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::Connection;
+use validator::Validate;
+
+use crate::elements::model::{Element, ElementPayload};
+
+/// Max number of items in a bulk request.
+pub const MAX_BULK_ITEMS: usize = 1000;
+
+/// Max size of the body of a bulk request, in bytes.
+pub const MAX_BULK_SIZE: usize = 16 * 1024 * 1024;
+
+/// Content type of the bodies with a JSON document by line.
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// How the items of a bulk request are applied.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkMode {
+    /// All the items are applied or none, stopping at the first error.
+    #[default]
+    Atomic,
+    /// Items are applied independently, the ones with errors are skipped.
+    BestEffort,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct BulkQuery {
+    #[serde(default)]
+    pub mode: BulkMode,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum BulkAction {
+    /// Create the element, like `POST /{tenant}`.
+    Create { element: ElementPayload },
+    /// Create or replace the element, like `PUT /{tenant}/{id}`.
+    Upsert { element: ElementPayload },
+    /// Delete the element, like `DELETE /{tenant}/{id}`.
+    Delete {
+        id: String,
+        #[serde(default)]
+        force: bool,
+    },
+}
+
+/// Result of an item, with the status code and error that the
+/// equivalent single element request would respond.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BulkItemResult {
+    /// Action of the item, `None` if not provided.
+    pub action: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<Value>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BulkResult {
+    /// Whether any item failed.
+    pub errors: bool,
+    pub items: Vec<BulkItemResult>,
+}
+
+/// Parse the body of a bulk request, a JSON array or
+/// a JSON document by line (NDJSON) with the items.
+pub fn parse_items(body: &[u8], ndjson: bool) -> Result<Vec<Value>> {
+    let items: Vec<Value> = if ndjson {
+        std::str::from_utf8(body)
+            .map_err(|_| AppError::StaticValidation("invalid UTF-8 body"))?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(i, line)| serde_json::from_str(line).map_err(|e| AppError::Validation(
+                Some("invalid_json"), format!("invalid JSON in line {}: {e}", i + 1)
+            )))
+            .collect::<Result<_>>()?
+    } else {
+        serde_json::from_slice(body).map_err(|e| AppError::Validation(
+            Some("invalid_json"), format!("a JSON array with the items is expected: {e}")
+        ))?
+    };
+    if items.is_empty() {
+        return Err(AppError::StaticValidation("no items provided"));
+    }
+    if items.len() > MAX_BULK_ITEMS {
+        return Err(AppError::Validation(
+            Some("too_many_items"),
+            format!("up to {MAX_BULK_ITEMS} items are allowed"),
+        ));
+    }
+    Ok(items)
+}
+
+/// Error serialized as it's responded by the endpoints.
+async fn error_payload(error: &AppError) -> (u16, Value) {
+    let body = to_bytes(error.error_response().into_body()).await.unwrap_or_default();
+    (error.status_code().as_u16(), serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+/// Apply the action, returning the status code and the id of the element.
+async fn apply(
+    tx: &mut Tx<'_>,
+    tid: &str,
+    action: BulkAction,
+    by: Option<&str>,
+) -> Result<(StatusCode, String)> {
+    match action {
+        BulkAction::Create { element } => {
+            let el = Element::insert(tx, tid, element, by).await?;
+            Ok((StatusCode::CREATED, el.id))
+        }
+        BulkAction::Upsert { element } => {
+            let Some(id) = element.id.clone() else {
+                return Err(AppError::StaticValidation("id is required to upsert"));
+            };
+            let el = Element::save(tx, tid, id.as_str(), element, by).await?;
+            Ok((StatusCode::OK, el.id))
+        }
+        BulkAction::Delete { id, force } => {
+            match Element::delete(tx, tid, id.as_str(), force).await? {
+                0 => Err(AppError::ResourceNotFound {
+                    resource: "element",
+                    attribute: "id",
+                    value: id,
+                }),
+                _ => Ok((StatusCode::NO_CONTENT, id)),
+            }
+        }
+    }
+}
+
+/// Action and id of an item, as they are in the item even if it's not valid.
+fn item_keys(item: &Value) -> (Option<String>, Option<String>) {
+    let action = item.get("action").and_then(Value::as_str).map(str::to_string);
+    let id = item.get("id")
+        .or_else(|| item.get("element").and_then(|el| el.get("id")))
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    (action, id)
+}
+
+/// Apply the item, returning the status code and the id of the element,
+/// or the status code and the error.
+async fn apply_item(
+    tx: &mut Tx<'_>,
+    tid: &str,
+    item: Value,
+    mode: BulkMode,
+    by: Option<&str>,
+) -> Result<core::result::Result<(StatusCode, String), (u16, Value)>> {
+    let action = match serde_json::from_value::<BulkAction>(item) {
+        Ok(action) => action,
+        Err(e) => {
+            let error = ValidationErrorPayload::with_code(
+                "invalid_item".to_string(), format!("invalid item: {e}"));
+            return Ok(Err((StatusCode::BAD_REQUEST.as_u16(), json!(error))));
+        }
+    };
+    let field_errors = match &action {
+        BulkAction::Create { element } | BulkAction::Upsert { element } =>
+            Validate::validate(element).err(),
+        BulkAction::Delete { .. } => None,
+    };
+    if let Some(field_errors) = field_errors {
+        let error = ValidationErrorPayload::from(&field_errors);
+        return Ok(Err((StatusCode::BAD_REQUEST.as_u16(), json!(error))));
+    }
+    let res = match mode {
+        BulkMode::Atomic => apply(tx, tid, action, by).await,
+        BulkMode::BestEffort => {
+            let mut savepoint = Connection::begin(&mut **tx).await.map_err(AppError::DB)?;
+            let res = apply(&mut savepoint, tid, action, by).await;
+            match res {
+                Ok(_) => savepoint.commit().await.map_err(AppError::DB)?,
+                Err(_) => savepoint.rollback().await.map_err(AppError::DB)?,
+            }
+            res
+        }
+    };
+    match res {
+        Ok(ok) => Ok(Ok(ok)),
+        Err(e) => Ok(Err(error_payload(&e).await)),
+    }
+}
+
+/// Apply the items of a bulk request, on behalf of the principal `by` if any.
+/// In atomic mode it stops at the first error, and the transaction has to be
+/// rolled back if there are errors. In best effort mode, each item is applied
+/// within a savepoint, so the transaction can be committed with the items that succeeded.
+pub async fn execute(
+    tx: &mut Tx<'_>,
+    tid: &str,
+    items: Vec<Value>,
+    mode: BulkMode,
+    by: Option<&str>,
+) -> Result<BulkResult> {
+    let mut results: Vec<BulkItemResult> = Vec::with_capacity(items.len());
+    let mut failed: Option<usize> = None;
+    for (i, item) in items.into_iter().enumerate() {
+        let (action, id) = item_keys(&item);
+        if mode == BulkMode::Atomic && failed.is_some() {
+            // Not processed, marked below as not applied
+            results.push(BulkItemResult { action, id, status: 0, error: None });
+            continue;
+        }
+        match apply_item(tx, tid, item, mode, by).await? {
+            Ok((status, id)) => results.push(BulkItemResult {
+                action, id: Some(id), status: status.as_u16(), error: None,
+            }),
+            Err((status, error)) => {
+                results.push(BulkItemResult { action, id, status, error: Some(error) });
+                failed.get_or_insert(i);
+            }
+        }
+    }
+    if let (BulkMode::Atomic, Some(failed)) = (mode, failed) {
+        // Nothing is applied, the rest of the items are marked
+        // as failed because of the item that failed
+        for (i, result) in results.iter_mut().enumerate() {
+            if i != failed {
+                result.status = StatusCode::FAILED_DEPENDENCY.as_u16();
+                result.error = Some(json!(ValidationErrorPayload::with_code(
+                    "not_applied".to_string(),
+                    "not applied because another item failed".to_string(),
+                )));
+            }
+        }
+    }
+    Ok(BulkResult { errors: failed.is_some(), items: results })
+}
@@ -0,0 +1,281 @@
+//! Filters over the attributes of the elements, parsed from query strings
+//! like `?filter[color]=red&filter[size][gt]=10`, and translated into
+//! parameterized JSONB predicates, plus the full-text search with `?q=`
+//! the elements updated since a time with `?updated_since=`, and the
+//! elements in the trash with `?include_deleted=true`.
+
+use actix_contrib_rest::result::{AppError, Result};
+use chrono::{DateTime, NaiveDateTime};
+use serde_json::Value;
+use sqlx::postgres::PgArguments;
+use sqlx::query::QueryAs;
+use sqlx::types::Json;
+use sqlx::Postgres;
+
+/// Max number of filters allowed in a single query.
+pub const MAX_FILTERS: usize = 20;
+
+/// Max number of levels of a nested attribute path, e.g. `dims.width` has 2.
+pub const MAX_PATH_DEPTH: usize = 10;
+
+/// Max number of words used in a full-text search, the rest are ignored.
+pub const MAX_SEARCH_TERMS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Exists,
+    In,
+}
+
+impl FilterOp {
+    fn parse(op: &str) -> Option<Self> {
+        match op {
+            "eq" => Some(FilterOp::Eq),
+            "ne" => Some(FilterOp::Ne),
+            "gt" => Some(FilterOp::Gt),
+            "gte" => Some(FilterOp::Gte),
+            "lt" => Some(FilterOp::Lt),
+            "lte" => Some(FilterOp::Lte),
+            "exists" => Some(FilterOp::Exists),
+            "in" => Some(FilterOp::In),
+            _ => None,
+        }
+    }
+
+    /// SQL predicate where `p` is the param with the
+    /// attribute path and `v` the param with the value.
+    fn as_sql(&self, p: usize, v: usize) -> String {
+        match self {
+            FilterOp::Eq => format!("data #> ${p} = ${v}"),
+            FilterOp::Ne => format!("data #> ${p} IS DISTINCT FROM ${v}"),
+            FilterOp::Gt | FilterOp::Gte | FilterOp::Lt | FilterOp::Lte => {
+                let cmp = match self {
+                    FilterOp::Gt => ">",
+                    FilterOp::Gte => ">=",
+                    FilterOp::Lt => "<",
+                    _ => "<=",
+                };
+                // Only compare values of the same type, e.g. numbers with numbers
+                format!("(jsonb_typeof(data #> ${p}) = jsonb_typeof(${v}) AND data #> ${p} {cmp} ${v})")
+            }
+            FilterOp::Exists => format!("(data #> ${p} IS NOT NULL) = (${v})::BOOLEAN"),
+            FilterOp::In => format!("data #> ${p} IN (SELECT jsonb_array_elements(${v}))"),
+        }
+    }
+}
+
+/// A single condition over an attribute of the elements.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    pub path: Vec<String>,
+    pub op: FilterOp,
+    pub value: Value,
+}
+
+/// Conditions over the attributes of the elements, all of them
+/// have to be met by an element to be included in the results.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ElementFilter {
+    pub filters: Vec<Filter>,
+    /// Full-text search query in the `tsquery` format.
+    pub search: Option<String>,
+    /// Only the elements updated at this time or after, in UTC.
+    pub updated_since: Option<NaiveDateTime>,
+    /// Whether to include the elements in the trash.
+    pub include_deleted: bool,
+}
+
+/// Parse a value from the query string: valid JSON scalars like `10`, `true`,
+/// `null` or `"10"` are used as JSON, anything else is taken as a string.
+fn parse_value(value: &str) -> Value {
+    match serde_json::from_str::<Value>(value) {
+        Ok(v) if !v.is_object() && !v.is_array() => v,
+        _ => Value::String(value.to_string()),
+    }
+}
+
+/// Translate the words of a search into a `tsquery` where all the words
+/// have to match, as prefixes of the words in the elements.
+///
+/// ```
+/// use backset::elements::filter::search_as_tsquery;
+/// assert_eq!(search_as_tsquery("red app"), Some("red:* & app:*".to_string()));
+/// assert_eq!(search_as_tsquery(" 'x' | !y:*"), Some("x:* & y:*".to_string()));
+/// assert_eq!(search_as_tsquery(" & "), None);
+/// ```
+pub fn search_as_tsquery(q: &str) -> Option<String> {
+    let terms: Vec<String> = q
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .take(MAX_SEARCH_TERMS)
+        .map(|t| format!("{t}:*"))
+        .collect();
+    match terms.len() {
+        0 => None,
+        _ => Some(terms.join(" & ")),
+    }
+}
+
+fn invalid_filter(error: String) -> AppError {
+    AppError::Validation(Some("invalid_filter"), error)
+}
+
+/// Parse a time in the RFC 3339 format, taken as UTC if it doesn't have offset.
+///
+/// ```
+/// use backset::elements::filter::parse_time;
+/// let time = parse_time("2024-01-02T10:00:00.5").unwrap();
+/// assert_eq!(time.to_string(), "2024-01-02 10:00:00.500");
+/// let time = parse_time("2024-01-02T10:00:00-03:00").unwrap();
+/// assert_eq!(time.to_string(), "2024-01-02 13:00:00");
+/// assert!(parse_time("2024-01-02").is_none());
+/// ```
+pub fn parse_time(time: &str) -> Option<NaiveDateTime> {
+    DateTime::parse_from_rfc3339(time)
+        .map(|t| t.naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M:%S%.f"))
+        .ok()
+}
+
+impl ElementFilter {
+    /// Parse all the `filter[...]` arguments, the `q` search, `updated_since` and
+    /// `include_deleted` of the query string pairs, ignoring any other argument.
+    ///
+    /// ```
+    /// use backset::elements::filter::{ElementFilter, FilterOp};
+    /// use serde_json::json;
+    /// let args = vec![
+    ///     ("filter[color]".to_string(), "red".to_string()),
+    ///     ("filter[dims.width][gt]".to_string(), "10".to_string()),
+    ///     ("page_size".to_string(), "5".to_string()),
+    /// ];
+    /// let filter = ElementFilter::parse(&args).unwrap();
+    /// assert_eq!(filter.filters.len(), 2);
+    /// assert_eq!(filter.filters[0].path, vec!["color"]);
+    /// assert_eq!(filter.filters[0].value, json!("red"));
+    /// assert_eq!(filter.filters[1].path, vec!["dims", "width"]);
+    /// assert_eq!(filter.filters[1].op, FilterOp::Gt);
+    /// assert_eq!(filter.filters[1].value, json!(10));
+    /// ```
+    pub fn parse(args: &[(String, String)]) -> Result<Self> {
+        let mut filters = Vec::new();
+        let mut search = None;
+        let mut updated_since = None;
+        let mut include_deleted = false;
+        for (key, value) in args.iter() {
+            if key == "q" {
+                search = search_as_tsquery(value);
+                continue;
+            }
+            if key == "updated_since" {
+                updated_since = Some(parse_time(value).ok_or_else(|| invalid_filter(
+                    format!("invalid \"updated_since\" time \"{value}\", RFC 3339 format is expected")
+                ))?);
+                continue;
+            }
+            if key == "include_deleted" {
+                include_deleted = match value.as_str() {
+                    "true" => true,
+                    "false" => false,
+                    _ => return Err(invalid_filter(
+                        "argument \"include_deleted\" only accepts \"true\" or \"false\"".to_string()
+                    )),
+                };
+                continue;
+            }
+            let Some(key) = key.strip_prefix("filter[") else {
+                continue;
+            };
+            let (path, op) = match key.split_once("][") {
+                Some((path, op)) => match op.strip_suffix(']') {
+                    Some(op) => (path, op),
+                    None => return Err(invalid_filter(format!("invalid filter \"filter[{key}\""))),
+                },
+                None => match key.strip_suffix(']') {
+                    Some(path) => (path, "eq"),
+                    None => return Err(invalid_filter(format!("invalid filter \"filter[{key}\""))),
+                },
+            };
+            let op = FilterOp::parse(op).ok_or_else(|| {
+                invalid_filter(format!("invalid filter operator \"{op}\""))
+            })?;
+            let path: Vec<String> = path.split('.').map(String::from).collect();
+            if path.iter().any(|p| p.is_empty()) || path.len() > MAX_PATH_DEPTH {
+                return Err(invalid_filter(format!("invalid filter attribute \"{}\"", path.join("."))));
+            }
+            let value = match op {
+                FilterOp::Exists => match value.as_str() {
+                    "true" => Value::Bool(true),
+                    "false" => Value::Bool(false),
+                    _ => return Err(invalid_filter(
+                        "the \"exists\" filter operator only accepts \"true\" or \"false\"".to_string()
+                    )),
+                },
+                FilterOp::In => match serde_json::from_str::<Value>(value) {
+                    Ok(Value::Array(values)) => Value::Array(values),
+                    _ => Value::Array(value.split(',').map(parse_value).collect()),
+                },
+                _ => parse_value(value),
+            };
+            filters.push(Filter { path, op, value });
+        }
+        if filters.len() > MAX_FILTERS {
+            return Err(invalid_filter(format!("too many filters, max allowed is {MAX_FILTERS}")));
+        }
+        Ok(ElementFilter { filters, search, updated_since, include_deleted })
+    }
+
+    /// SQL conditions to append to a `WHERE` clause, each starting with `AND`,
+    /// where the first param used is `$first_param`.
+    pub fn as_sql(&self, first_param: usize) -> String {
+        let mut sql = String::new();
+        let mut first_param = first_param;
+        if !self.include_deleted {
+            sql.push_str(" AND deleted_at IS NULL");
+        }
+        if self.search.is_some() {
+            sql.push_str(&format!(" AND search @@ to_tsquery('simple', ${first_param})"));
+            first_param += 1;
+        }
+        if self.updated_since.is_some() {
+            sql.push_str(&format!(" AND updated_at >= ${first_param}"));
+            first_param += 1;
+        }
+        for (i, f) in self.filters.iter().enumerate() {
+            let p = first_param + i * 2;
+            sql.push_str(&format!(" AND {}", f.op.as_sql(p, p + 1)));
+        }
+        sql
+    }
+
+    /// SQL expression to sort by relevance the results of the search, if any,
+    /// where `$first_param` is the same param passed to [`ElementFilter::as_sql()`].
+    pub fn rank_sql(&self, first_param: usize) -> Option<String> {
+        self.search.as_ref().map(|_| {
+            format!("ts_rank(search, to_tsquery('simple', ${first_param})) DESC")
+        })
+    }
+
+    /// Bind the params of the conditions returned by [`ElementFilter::as_sql()`].
+    pub fn bind<'q, O>(
+        &'q self,
+        mut query: QueryAs<'q, Postgres, O, PgArguments>,
+    ) -> QueryAs<'q, Postgres, O, PgArguments> {
+        if let Some(search) = self.search.as_ref() {
+            query = query.bind(search);
+        }
+        if let Some(updated_since) = self.updated_since {
+            query = query.bind(updated_since);
+        }
+        for f in self.filters.iter() {
+            query = query.bind(&f.path).bind(Json(&f.value));
+        }
+        query
+    }
+}
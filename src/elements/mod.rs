@@ -1,2 +1,5 @@
 pub mod api;
+pub mod bulk;
+pub mod filter;
 pub mod model;
+pub mod revisions;
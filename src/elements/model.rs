@@ -1,25 +1,58 @@
 use actix_contrib_rest::db::Tx;
 use actix_contrib_rest::query::QuerySearch;
 use actix_contrib_rest::result::{AppError, Result};
-use chrono::NaiveDateTime;
-use rand::random;
+use chrono::{NaiveDateTime, TimeDelta};
+use json_patch::{Patch, PatchErrorKind};
 use regex::Regex;
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Serialize, Serializer};
 use serde_json::{Map, Value};
 use sqlx::postgres::PgQueryResult;
 use sqlx::types::Json;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::sync::LazyLock;
-use validator::Validate;
+use tracing::instrument;
+use validator::{Validate, ValidationError};
 
+use crate::elements::filter::ElementFilter;
+use crate::elements::revisions::ElementRevision;
+use crate::relationships::model::Relationship;
+use crate::schemas::model::Schema;
 use crate::tenants::model::Tenant;
-use crate::utils::reject_created_at;
+use crate::utils::{reject_reserved_attributes, serialize_attributes};
 
 // Base64 URL characters (except =) and some others like \~@-.:+
-static ID_VALID: LazyLock<Regex> = LazyLock::new(|| {
+pub(crate) static ID_VALID: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(r"^(?i)[a-z0-9_~@\\/][a-z0-9_\\~@\-\.\:+]*$").unwrap()
 });
 
-#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+// Attributes paths that can be used to sort, e.g. "data.priority" or "data.dims.width"
+static SORT_PATH_VALID: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^data(\.[a-zA-Z0-9_\-]+)+$").unwrap()
+});
+
+fn validate_forbidden_list(id: &str) -> core::result::Result<(), ValidationError> {
+    if id == "sets" {
+        // id cannot collide with endpoint paths
+        return Err(ValidationError {
+            code: Cow::from("forbidden_id"),
+            message: Some(Cow::from("Forbidden element id.")),
+            params: HashMap::new(),
+        });
+    }
+    Ok(())
+}
+
+/// Check the id of an element passed in the path is not forbidden,
+/// as the ids of the payloads are.
+fn forbidden_id_or_fail(id: &str) -> Result<()> {
+    validate_forbidden_list(id).map_err(|_| {
+        AppError::Validation(Some("forbidden_id"), format!("forbidden element id \"{id}\""))
+    })
+}
+
+#[derive(Debug, Deserialize, sqlx::FromRow, Clone)]
 pub struct Element {
     pub id: String,
     #[serde(skip_serializing)]
@@ -27,11 +60,51 @@ pub struct Element {
     #[serde(flatten)]
     pub data: Json<Map<String, Value>>,
     pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    /// Id of the principal that updated the element last, if authenticated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_by: Option<String>,
+    /// Incremented on each update, it's responded as `ETag` but it's not
+    /// serialized, so it doesn't collide with an attribute "version".
+    #[serde(skip_serializing, default)]
+    pub version: i64,
+    /// Time when the element was moved to the trash, if it was.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[sqlx(default)]
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl Serialize for Element {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        let mut fields = vec!["id", "created_at", "updated_at"];
+        if self.updated_by.is_some() {
+            fields.push("updated_by");
+        }
+        if self.deleted_at.is_some() {
+            fields.push("deleted_at");
+        }
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("id", &self.id)?;
+        serialize_attributes(&mut map, &self.data, &fields)?;
+        map.serialize_entry("created_at", &self.created_at)?;
+        map.serialize_entry("updated_at", &self.updated_at)?;
+        if let Some(updated_by) = self.updated_by.as_ref() {
+            map.serialize_entry("updated_by", updated_by)?;
+        }
+        if let Some(deleted_at) = self.deleted_at.as_ref() {
+            map.serialize_entry("deleted_at", deleted_at)?;
+        }
+        map.end()
+    }
 }
 
+/// Keys of an element used as cursor, in the default order of the listings.
+pub type ElementCursor = (NaiveDateTime, String);
+
 #[derive(Deserialize, Validate)]
 pub struct ElementPayload {
     #[validate(length(min = 1, max = 256))]
+    #[validate(custom(function = "validate_forbidden_list"))]
     #[validate(regex(
         path = *ID_VALID,
         code = "invalid_id",
@@ -45,66 +118,310 @@ pub struct ElementPayload {
 
 impl ElementPayload {
     pub fn validate(&self) -> Result<()> {
-        reject_created_at(&self.data)
+        reject_reserved_attributes(&self.data)
     }
 }
 
+/// Content type of the RFC 7396 JSON Merge Patch documents.
+pub const MERGE_PATCH_CONTENT_TYPE: &str = "application/merge-patch+json";
+
+/// Content type of the RFC 6902 JSON Patch documents.
+pub const JSON_PATCH_CONTENT_TYPE: &str = "application/json-patch+json";
+
+/// Partial update of an element, applied over the element with
+/// its id and attributes, but without its creation time.
+pub enum ElementPatch {
+    /// JSON Merge Patch (RFC 7396), a document with the attributes
+    /// to replace, or to remove if `null`.
+    Merge(Value),
+    /// JSON Patch (RFC 6902), a list of operations.
+    Json(Patch),
+}
+
+impl ElementPatch {
+    /// Parse the body with the format of the content type passed, or `None`
+    /// if the content type is not one of the patch formats supported.
+    pub fn parse(content_type: &str, body: &[u8]) -> Option<Result<Self>> {
+        let res = match content_type {
+            MERGE_PATCH_CONTENT_TYPE => serde_json::from_slice(body).map(ElementPatch::Merge),
+            JSON_PATCH_CONTENT_TYPE => serde_json::from_slice(body).map(ElementPatch::Json),
+            _ => return None,
+        };
+        Some(res.map_err(|e| AppError::Validation(Some("invalid_patch"), format!("invalid patch: {e}"))))
+    }
+
+    /// Apply the patch to the document, all the operations or none.
+    fn apply(&self, doc: &mut Value) -> Result<()> {
+        match self {
+            ElementPatch::Merge(patch) => json_patch::merge(doc, patch),
+            ElementPatch::Json(patch) => json_patch::patch(doc, patch).map_err(|e| {
+                let code = match e.kind {
+                    PatchErrorKind::TestFailed => "patch_test_failed",
+                    _ => "invalid_patch",
+                };
+                AppError::Validation(Some(code), e.to_string())
+            })?,
+        }
+        Ok(())
+    }
+}
+
+/// Element as it's exported and imported, with its creation time.
+#[derive(Debug, Deserialize, Validate)]
+pub struct ElementRecord {
+    #[validate(length(min = 1, max = 256))]
+    #[validate(custom(function = "validate_forbidden_list"))]
+    #[validate(regex(path = *ID_VALID, code = "invalid_id"))]
+    pub id: String,
+    #[serde(flatten)]
+    pub data: Json<Map<String, Value>>,
+    pub created_at: NaiveDateTime,
+    /// The creation time if not provided, e.g. in exports of previous versions.
+    #[serde(default)]
+    pub updated_at: Option<NaiveDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_by: Option<String>,
+}
+
+impl Serialize for ElementRecord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        let mut fields = vec!["id", "created_at"];
+        if self.updated_at.is_some() {
+            fields.push("updated_at");
+        }
+        if self.updated_by.is_some() {
+            fields.push("updated_by");
+        }
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("id", &self.id)?;
+        serialize_attributes(&mut map, &self.data, &fields)?;
+        map.serialize_entry("created_at", &self.created_at)?;
+        if let Some(updated_at) = self.updated_at.as_ref() {
+            map.serialize_entry("updated_at", updated_at)?;
+        }
+        if let Some(updated_by) = self.updated_by.as_ref() {
+            map.serialize_entry("updated_by", updated_by)?;
+        }
+        map.end()
+    }
+}
+
+impl From<Element> for ElementRecord {
+    fn from(el: Element) -> Self {
+        ElementRecord {
+            id: el.id,
+            data: el.data,
+            created_at: el.created_at,
+            updated_at: Some(el.updated_at),
+            updated_by: el.updated_by,
+        }
+    }
+}
+
+/// Payload to get many elements by id.
+#[derive(Deserialize, Serialize, Validate)]
+pub struct ElementIdsPayload {
+    #[validate(length(min = 1, max = 1000))]
+    pub ids: Vec<String>,
+}
+
+/// Elements found by id, in the order requested, and the ids not found.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ElementsFound<T = Element> {
+    pub data: Vec<T>,
+    pub missing: Vec<String>,
+}
+
+/// Parse the sort argument "col1,-data.attr..." into a SQL `ORDER BY` expression.
+/// Only the columns "id", "created_at" and "updated_at", and attributes paths starting with
+/// "data." are allowed, the rest are ignored. Sorting by "id" is always added
+/// at the end to break ties, so pagination is stable.
+///
+/// ```
+/// use actix_contrib_rest::query::QuerySearch;
+/// use backset::elements::model::sort_as_order_by_args;
+/// let q = QuerySearch { q: None, offset: 0, page_size: 10, sort: None, include_total: None };
+/// assert_eq!(sort_as_order_by_args(&q), "created_at DESC, id");
+/// let q = QuerySearch { q: None, offset: 0, page_size: 10, sort: Some(String::from("-data.a.b,id,x")), include_total: None };
+/// assert_eq!(sort_as_order_by_args(&q), "data #> '{a,b}' DESC NULLS LAST, id");
+/// let q = QuerySearch { q: None, offset: 0, page_size: 10, sort: Some(String::from("data.a',created_at")), include_total: None };
+/// assert_eq!(sort_as_order_by_args(&q), "created_at, id");
+/// ```
+pub fn sort_as_order_by_args(query: &QuerySearch) -> String {
+    let mut args: Vec<String> = Vec::new();
+    let mut has_id = false;
+    for field in query.sort.as_deref().unwrap_or("").split(',') {
+        let (name, order) = match field.strip_prefix('-') {
+            Some(name) => (name, " DESC"),
+            None => (field, ""),
+        };
+        match name {
+            "id" | "created_at" | "updated_at" => {
+                has_id |= name == "id";
+                args.push(format!("{name}{order}"));
+            }
+            _ if SORT_PATH_VALID.is_match(name) => {
+                let path = name["data.".len()..].replace('.', ",");
+                args.push(format!("data #> '{{{path}}}'{order} NULLS LAST"));
+            }
+            _ => {}
+        }
+    }
+    if args.is_empty() {
+        args.push(String::from("created_at DESC"));
+    }
+    if !has_id {
+        args.push(String::from("id"));
+    }
+    args.join(", ")
+}
+
 impl Element {
-    pub async fn insert(tx: &mut Tx<'_>, tid: &str, el_form: ElementPayload) -> Result<Element> {
+    #[instrument(name = "Element::insert", skip_all, fields(tid = %tid))]
+    pub async fn insert(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        el_form: ElementPayload,
+        by: Option<&str>,
+    ) -> Result<Element> {
         el_form.validate()?;
         Tenant::exists_or_fail(tx, tid).await?;
+        // New elements don't belong to any set, only the tenant schema applies
+        Schema::validate_element(tx, tid, None, &el_form.data).await?;
         let id = match el_form.id {
-            None => random::<u64>().to_string(),
-            Some(_id) => {
-                let exists = Self::exists(tx, tid, _id.as_str()).await?;
-                if exists {
-                    return Err(AppError::ResourceAlreadyExists {
-                        resource: "element",
-                        attribute: "id",
-                        value: _id,
-                    });
-                }
-                _id
-            }
+            None => Tenant::next_element_id(tx, tid, &el_form.data).await?,
+            Some(_id) => _id,
         };
+        // Generated ids are checked too, e.g. the "hash" strategy
+        // generates the same id for elements with the same content.
+        // Elements in the trash keep their id until they are purged
+        if Self::exists(tx, tid, id.as_str(), true).await? {
+            return Err(AppError::ResourceAlreadyExists {
+                resource: "element",
+                attribute: "id",
+                value: id,
+            });
+        }
+        // Versions go on after the revisions of a deleted element with the same id
         let element = sqlx::query_as::<_, Element>(
-            "INSERT INTO elements (tid, id, data, created_at) \
-            VALUES ($1, $2, $3, NOW()) RETURNING *",
+                r#"
+            INSERT INTO elements (tid, id, data, created_at, updated_at, updated_by, version)
+            VALUES ($1, $2, $3, NOW(), NOW(), $4,
+              (SELECT COALESCE(MAX(version), 0) + 1 FROM element_revisions WHERE tid = $1 AND id = $2))
+            RETURNING *
+                "#
             )
             .bind(tid)
             .bind(id.as_str())
             .bind(el_form.data)
+            .bind(by)
             .fetch_one(&mut **tx)
             .await
             .map_err(AppError::DB)?;
         Ok(element)
     }
 
-    pub async fn exists(tx: &mut Tx<'_>, tid: &str, id: &str) -> Result<bool> {
+    #[instrument(name = "Element::exists", skip_all, fields(tid = %tid))]
+    pub async fn exists(tx: &mut Tx<'_>, tid: &str, id: &str, include_deleted: bool) -> Result<bool> {
         let res: (bool,) = sqlx::query_as(
-            "SELECT EXISTS(SELECT id FROM elements WHERE tid = $1 AND id = $2)")
+            "SELECT EXISTS(SELECT id FROM elements WHERE tid = $1 AND id = $2 AND ($3 OR deleted_at IS NULL))")
             .bind(tid)
             .bind(id)
+            .bind(include_deleted)
             .fetch_one(&mut **tx)
             .await
             .map_err(AppError::DB)?;
         Ok(res.0)
     }
 
-    pub async fn get(tx: &mut Tx<'_>, tid: &str, id: &str) -> Result<Option<Element>> {
+    /// Get the element, if `include_deleted` even if it's in the trash.
+    #[instrument(name = "Element::get", skip_all, fields(tid = %tid))]
+    pub async fn get(tx: &mut Tx<'_>, tid: &str, id: &str, include_deleted: bool) -> Result<Option<Element>> {
         Tenant::exists_or_fail(tx, tid).await?;
         let element: Option<Element> = sqlx::query_as(
-            "SELECT * FROM elements WHERE tid = $1 AND id = $2")
+            "SELECT * FROM elements WHERE tid = $1 AND id = $2 AND ($3 OR deleted_at IS NULL)")
             .bind(tid)
             .bind(id)
+            .bind(include_deleted)
             .fetch_optional(&mut **tx)
             .await
             .map_err(AppError::DB)?;
         Ok(element)
     }
 
-    pub async fn delete(tx: &mut Tx<'_>, tid: &str, id: &str) -> Result<u64> {
+    /// Get the elements with the `ids` given in one query, in the same order
+    /// of the ids and without duplicates, plus the ids not found.
+    #[instrument(name = "Element::get_many", skip_all, fields(tid = %tid))]
+    pub async fn get_many(tx: &mut Tx<'_>, tid: &str, ids: &[String]) -> Result<ElementsFound> {
+        Tenant::exists_or_fail(tx, tid).await?;
+        let elements: Vec<Element> = sqlx::query_as(
+            "SELECT * FROM elements WHERE tid = $1 AND id = ANY($2) AND deleted_at IS NULL")
+            .bind(tid)
+            .bind(ids)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        let mut by_id: HashMap<String, Element> = elements.into_iter()
+            .map(|el| (el.id.clone(), el))
+            .collect();
+        let mut found = ElementsFound { data: Vec::with_capacity(by_id.len()), missing: Vec::new() };
+        let mut seen: HashSet<&str> = HashSet::with_capacity(ids.len());
+        for id in ids.iter().filter(|id| seen.insert(id.as_str())) {
+            match by_id.remove(id) {
+                Some(el) => found.data.push(el),
+                None => found.missing.push(id.clone()),
+            }
+        }
+        Ok(found)
+    }
+
+    /// Get the version of the element locking it until the transaction ends,
+    /// so it can be checked before updating it, or `None` if not found.
+    #[instrument(name = "Element::lock_version", skip_all, fields(tid = %tid))]
+    pub async fn lock_version(tx: &mut Tx<'_>, tid: &str, id: &str) -> Result<Option<i64>> {
+        Tenant::exists_or_fail(tx, tid).await?;
+        let res: Option<(i64,)> = sqlx::query_as(
+            "SELECT version FROM elements WHERE tid = $1 AND id = $2 AND deleted_at IS NULL FOR UPDATE")
+            .bind(tid)
+            .bind(id)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(res.map(|r| r.0))
+    }
+
+    #[instrument(name = "Element::has_relationships", skip_all, fields(tid = %tid))]
+    pub async fn has_relationships(tx: &mut Tx<'_>, tid: &str, id: &str) -> Result<bool> {
+        let res: (bool,) = sqlx::query_as(
+                r#"
+            SELECT EXISTS(
+              SELECT from_id FROM relationships WHERE tid = $1 AND (from_id = $2 OR to_id = $2)
+            )
+                "#
+            )
+            .bind(tid)
+            .bind(id)
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(res.0)
+    }
+
+    #[instrument(name = "Element::delete", skip_all, fields(tid = %tid))]
+    pub async fn delete(tx: &mut Tx<'_>, tid: &str, id: &str, force: bool) -> Result<u64> {
         Tenant::exists_or_fail(tx, tid).await?;
+        let mut rows_affected: u64 = 0;
+        if force {
+            rows_affected += Relationship::delete_all(tx, tid, id).await?;
+        } else {
+            let has_rel = Self::has_relationships(tx, tid, id).await?;
+            if has_rel {
+                return Err(AppError::StaticValidation(
+                    "cannot delete element with relationships",
+                ));
+            }
+        }
+        ElementRevision::archive(tx, tid, id, true).await?;
         let res: PgQueryResult = sqlx::query(
                 "DELETE FROM elements WHERE tid = $1 AND id = $2")
             .bind(tid)
@@ -112,60 +429,300 @@ impl Element {
             .execute(&mut **tx)
             .await
             .map_err(AppError::DB)?;
+        rows_affected += res.rows_affected();
+
+        Ok(rows_affected)
+    }
 
+    /// Move the element to the trash as a new version, keeping its
+    /// relationships and sets, returning 0 if not found or already there.
+    #[instrument(name = "Element::soft_delete", skip_all, fields(tid = %tid))]
+    pub async fn soft_delete(tx: &mut Tx<'_>, tid: &str, id: &str) -> Result<u64> {
+        Tenant::exists_or_fail(tx, tid).await?;
+        if Self::lock_version(tx, tid, id).await?.is_none() {
+            return Ok(0);
+        }
+        ElementRevision::archive(tx, tid, id, false).await?;
+        let res: PgQueryResult = sqlx::query(
+                r#"
+            UPDATE elements SET deleted_at = NOW(), version = version + 1
+            WHERE tid = $1 AND id = $2 AND deleted_at IS NULL
+                "#
+            )
+            .bind(tid)
+            .bind(id)
+            .execute(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
         Ok(res.rows_affected())
     }
 
-    pub async fn count(tx: &mut Tx<'_>, tid: &str) -> Result<i64> {
-        let count: (i64,) = sqlx::query_as(
-            "SELECT COUNT(*) FROM elements WHERE tid = $1"
-        )
+    /// Restore the element from the trash as a new version, or `None` if it's
+    /// not there. The element is validated again with the schemas of the tenant,
+    /// that may have changed while it was in the trash.
+    #[instrument(name = "Element::restore", skip_all, fields(tid = %tid))]
+    pub async fn restore(tx: &mut Tx<'_>, tid: &str, id: &str) -> Result<Option<Element>> {
+        Tenant::exists_or_fail(tx, tid).await?;
+        let data: Option<(Json<Map<String, Value>>,)> = sqlx::query_as(
+                "SELECT data FROM elements WHERE tid = $1 AND id = $2 AND deleted_at IS NOT NULL FOR UPDATE")
+            .bind(tid)
+            .bind(id)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        let Some((data,)) = data else {
+            return Ok(None);
+        };
+        Schema::validate_element(tx, tid, Some(id), &data).await?;
+        ElementRevision::archive(tx, tid, id, true).await?;
+        let element: Element = sqlx::query_as(
+                r#"
+            UPDATE elements SET deleted_at = NULL, version = version + 1
+            WHERE tid = $1 AND id = $2
+            RETURNING *
+                "#
+            )
             .bind(tid)
+            .bind(id)
             .fetch_one(&mut **tx)
             .await
             .map_err(AppError::DB)?;
-        Ok(count.0)
+        Ok(Some(element))
     }
 
-    pub async fn find(tx: &mut Tx<'_>, tid: &str, query: &QuerySearch) -> Result<Vec<Element>> {
-        let query = sqlx::query_as(
+    /// Find the tenant and id of the elements moved to the trash before
+    /// `older_than` ago, except the ones of tenants in the trash.
+    #[instrument(name = "Element::find_deleted", skip_all)]
+    pub async fn find_deleted(tx: &mut Tx<'_>, older_than: TimeDelta) -> Result<Vec<(String, String)>> {
+        let keys: Vec<(String, String)> = sqlx::query_as(
                 r#"
+            SELECT e.tid, e.id
+            FROM elements e
+            JOIN tenants t ON t.id = e.tid AND t.deleted_at IS NULL
+            WHERE e.deleted_at < NOW() - $1
+            ORDER BY e.tid, e.id
+                "#
+            )
+            .bind(older_than)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(keys)
+    }
+
+    #[instrument(name = "Element::count", skip_all, fields(tid = %tid))]
+    pub async fn count(tx: &mut Tx<'_>, tid: &str, filter: &ElementFilter) -> Result<i64> {
+        let sql = format!(
+            "SELECT COUNT(*) FROM elements WHERE tid = $1{}",
+            filter.as_sql(2)
+        );
+        let query = sqlx::query_as(sql.as_str())
+            .bind(tid);
+        let count: (i64,) = filter.bind(query)
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(count.0)
+    }
+
+    /// Find the elements, if `after` is passed only the elements after it
+    /// in the default order are returned (cursor pagination).
+    #[instrument(name = "Element::find", skip_all, fields(tid = %tid))]
+    pub async fn find(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        query: &QuerySearch,
+        filter: &ElementFilter,
+        after: Option<&ElementCursor>,
+    ) -> Result<Vec<Element>> {
+        let (keyset, first_param) = match after {
+            Some(_) => (" AND (created_at < $4 OR (created_at = $4 AND id > $5))", 6),
+            None => ("", 4),
+        };
+        // Without an explicit sort, results from a search are sorted by relevance
+        let order = match filter.rank_sql(first_param) {
+            Some(rank) if query.sort.is_none() => format!("{rank}, {}", sort_as_order_by_args(query)),
+            _ => sort_as_order_by_args(query),
+        };
+        let sql = format!(
+            r#"
             SELECT *
             FROM elements
-            WHERE tid = $1
-            ORDER BY created_at DESC
+            WHERE tid = $1{keyset}{}
+            ORDER BY {order}
             LIMIT $2 OFFSET $3
-                "#
-            )
+            "#,
+            filter.as_sql(first_param)
+        );
+        let mut query = sqlx::query_as(sql.as_str())
             .bind(tid)
             .bind(query.page_size)
             .bind(query.offset);
-        let elements: Vec<Element> = query.fetch_all(&mut **tx)
+        if let Some((created_at, id)) = after {
+            query = query.bind(created_at).bind(id);
+        }
+        let elements: Vec<Element> = filter.bind(query)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(elements)
+    }
+
+    /// Apply the patch to the element, locking it until the transaction ends so
+    /// concurrent patches don't override each other, or `None` if not found.
+    #[instrument(name = "Element::patch", skip_all, fields(tid = %tid))]
+    pub async fn patch(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        id: &str,
+        patch: &ElementPatch,
+        by: Option<&str>,
+    ) -> Result<Option<Element>> {
+        forbidden_id_or_fail(id)?;
+        Tenant::exists_or_fail(tx, tid).await?;
+        let element: Option<Element> = sqlx::query_as(
+            "SELECT * FROM elements WHERE tid = $1 AND id = $2 AND deleted_at IS NULL FOR UPDATE")
+            .bind(tid)
+            .bind(id)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        let Some(element) = element else {
+            return Ok(None);
+        };
+        let mut doc = Value::Object(element.data.0);
+        doc["id"] = Value::String(element.id);
+        patch.apply(&mut doc)?;
+        let Value::Object(mut data) = doc else {
+            return Err(AppError::StaticValidation("the element patched has to be an object"));
+        };
+        match data.remove("id") {
+            Some(Value::String(patched_id)) if patched_id == id => {}
+            _ => return Err(AppError::StaticValidation("id mismatch")),
+        }
+        let data = Json(data);
+        reject_reserved_attributes(&data)?;
+        Schema::validate_element(tx, tid, Some(id), &data).await?;
+        ElementRevision::archive(tx, tid, id, true).await?;
+        let element = sqlx::query_as::<_, Element>(
+            r#"
+            UPDATE elements SET data = $3, updated_at = NOW(), updated_by = $4, version = version + 1
+            WHERE tid = $1 AND id = $2
+            RETURNING *
+            "#)
+            .bind(tid)
+            .bind(id)
+            .bind(data)
+            .bind(by)
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(Some(element))
+    }
+
+    /// Find the elements sorted by id, only the ones after the id `after` if passed,
+    /// so all the elements of a tenant can be read in batches, except the ones in the trash.
+    #[instrument(name = "Element::find_by_id", skip_all, fields(tid = %tid))]
+    pub async fn find_by_id(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        after: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<Element>> {
+        let elements: Vec<Element> = sqlx::query_as(
+                r#"
+            SELECT *
+            FROM elements
+            WHERE tid = $1 AND ($2::VARCHAR IS NULL OR id > $2) AND deleted_at IS NULL
+            ORDER BY id
+            LIMIT $3
+                "#
+            )
+            .bind(tid)
+            .bind(after)
+            .bind(limit)
+            .fetch_all(&mut **tx)
             .await
             .map_err(AppError::DB)?;
         Ok(elements)
     }
 
+    /// Insert the elements as they are, keeping their creation time, without
+    /// validating them against the schemas or generating ids.
+    #[instrument(name = "Element::insert_records", skip_all, fields(tid = %tid))]
+    pub async fn insert_records(tx: &mut Tx<'_>, tid: &str, records: Vec<ElementRecord>) -> Result<u64> {
+        let mut ids: Vec<String> = Vec::with_capacity(records.len());
+        let mut data: Vec<Json<Map<String, Value>>> = Vec::with_capacity(records.len());
+        let mut created_at: Vec<NaiveDateTime> = Vec::with_capacity(records.len());
+        let mut updated_at: Vec<Option<NaiveDateTime>> = Vec::with_capacity(records.len());
+        let mut updated_by: Vec<Option<String>> = Vec::with_capacity(records.len());
+        for record in records {
+            ids.push(record.id);
+            data.push(record.data);
+            created_at.push(record.created_at);
+            updated_at.push(record.updated_at);
+            updated_by.push(record.updated_by);
+        }
+        let res: PgQueryResult = sqlx::query(
+                r#"
+            INSERT INTO elements (tid, id, data, created_at, updated_at, updated_by)
+            SELECT $1, id, data, created_at, COALESCE(updated_at, created_at), updated_by
+            FROM UNNEST($2::VARCHAR[], $3::JSONB[], $4::TIMESTAMP[], $5::TIMESTAMP[], $6::VARCHAR[])
+              AS r(id, data, created_at, updated_at, updated_by)
+                "#
+            )
+            .bind(tid)
+            .bind(ids)
+            .bind(data)
+            .bind(created_at)
+            .bind(updated_at)
+            .bind(updated_by)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| match e.as_database_error() {
+                Some(db_error) if db_error.is_unique_violation() => AppError::StaticValidation(
+                    "elements with duplicated ids or already existing"
+                ),
+                _ => AppError::DB(e),
+            })?;
+        Ok(res.rows_affected())
+    }
+
+    /// Create or replace the element, restoring it if it's in the trash.
+    #[instrument(name = "Element::save", skip_all, fields(tid = %tid))]
     pub async fn save(
         tx: &mut Tx<'_>,
         tid: &str,
         id: &str,
-        el_form: ElementPayload
+        el_form: ElementPayload,
+        by: Option<&str>,
     ) -> Result<Element> {
         el_form.validate()?;
+        forbidden_id_or_fail(id)?;
         if el_form.id.map(|form_id| form_id.as_str() != id).unwrap_or(false) {
             return Err(AppError::StaticValidation("id mismatch"));
         }
         Tenant::exists_or_fail(tx, tid).await?;
+        Schema::validate_element(tx, tid, Some(id), &el_form.data).await?;
+        ElementRevision::archive(tx, tid, id, false).await?;
         let element = sqlx::query_as::<_, Element>(
-            "INSERT INTO elements (tid, id, data, created_at) \
-            VALUES ($1, $2, $3, NOW()) \
-            ON CONFLICT (tid,id) DO UPDATE SET data = EXCLUDED.data
-            RETURNING *",
+            r#"
+            INSERT INTO elements (tid, id, data, created_at, updated_at, updated_by, version)
+            VALUES ($1, $2, $3, NOW(), NOW(), $4,
+              (SELECT COALESCE(MAX(version), 0) + 1 FROM element_revisions WHERE tid = $1 AND id = $2))
+            ON CONFLICT (tid,id) DO UPDATE SET
+              data = EXCLUDED.data,
+              updated_at = EXCLUDED.updated_at,
+              updated_by = EXCLUDED.updated_by,
+              version = elements.version + 1,
+              deleted_at = NULL
+            RETURNING *
+            "#,
         )
             .bind(tid)
             .bind(id)
             .bind(el_form.data)
+            .bind(by)
             .fetch_one(&mut **tx)
             .await
             .map_err(AppError::DB)?;
@@ -0,0 +1,192 @@
+//! Revisions of the elements: each time an element is updated or deleted,
+//! the version replaced is kept, so the element can be read as it was at
+//! any time, and rewritten with the data of any of its prior versions.
+
+use actix_contrib_rest::db::Tx;
+use actix_contrib_rest::query::QuerySearch;
+use actix_contrib_rest::result::{AppError, Result};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgQueryResult;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use tracing::instrument;
+use validator::{Validate, ValidationError};
+
+use crate::elements::filter::parse_time;
+use crate::elements::model::{Element, ElementPayload};
+use crate::tenants::model::Tenant;
+
+/// Prior version of an element.
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct ElementRevision {
+    pub version: i64,
+    /// Time when the version stopped being the current one.
+    pub replaced_at: NaiveDateTime,
+    /// Whether the version was replaced by deleting the element, or was
+    /// the element in the trash.
+    pub deleted: bool,
+    /// The element as it was in the version.
+    #[sqlx(flatten)]
+    pub element: Element,
+}
+
+fn validate_time(time: &str) -> core::result::Result<(), ValidationError> {
+    if parse_time(time).is_none() {
+        return Err(ValidationError {
+            code: Cow::from("invalid_time"),
+            message: Some(Cow::from("Time in the RFC 3339 format expected.")),
+            params: HashMap::new(),
+        });
+    }
+    Ok(())
+}
+
+/// Query arguments to read an element as it was at a time.
+#[derive(Debug, Deserialize, Validate)]
+pub struct AtQuery {
+    #[validate(custom(function = "validate_time"))]
+    pub at: Option<String>,
+}
+
+impl AtQuery {
+    /// The time requested in UTC, if any.
+    pub fn time(&self) -> Option<NaiveDateTime> {
+        self.at.as_deref().and_then(parse_time)
+    }
+}
+
+impl ElementRevision {
+    /// Keep the current version of the element as a revision, before it's
+    /// replaced or `deleted`, also marked as `deleted` if it's the version
+    /// in the trash. The element is locked until the transaction
+    /// ends, so concurrent updates cannot keep the same version twice.
+    #[instrument(name = "ElementRevision::archive", skip_all, fields(tid = %tid))]
+    pub async fn archive(tx: &mut Tx<'_>, tid: &str, id: &str, deleted: bool) -> Result<u64> {
+        let res: PgQueryResult = sqlx::query(
+                r#"
+            INSERT INTO element_revisions
+              (tid, id, version, data, created_at, updated_at, updated_by, deleted_at, replaced_at, deleted)
+            SELECT tid, id, version, data, created_at, updated_at, updated_by, deleted_at, NOW(),
+                   $3 OR deleted_at IS NOT NULL
+            FROM elements
+            WHERE tid = $1 AND id = $2
+            FOR UPDATE
+                "#
+            )
+            .bind(tid)
+            .bind(id)
+            .bind(deleted)
+            .execute(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(res.rows_affected())
+    }
+
+    #[instrument(name = "ElementRevision::count", skip_all, fields(tid = %tid))]
+    pub async fn count(tx: &mut Tx<'_>, tid: &str, id: &str) -> Result<i64> {
+        let count: (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM element_revisions WHERE tid = $1 AND id = $2")
+            .bind(tid)
+            .bind(id)
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(count.0)
+    }
+
+    /// Find the revisions of the element, the newest first.
+    #[instrument(name = "ElementRevision::find", skip_all, fields(tid = %tid))]
+    pub async fn find(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        id: &str,
+        query: &QuerySearch,
+    ) -> Result<Vec<ElementRevision>> {
+        let revisions: Vec<ElementRevision> = sqlx::query_as(
+                r#"
+            SELECT *
+            FROM element_revisions
+            WHERE tid = $1 AND id = $2
+            ORDER BY version DESC
+            LIMIT $3 OFFSET $4
+                "#
+            )
+            .bind(tid)
+            .bind(id)
+            .bind(query.page_size)
+            .bind(query.offset)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(revisions)
+    }
+
+    #[instrument(name = "ElementRevision::get", skip_all, fields(tid = %tid))]
+    pub async fn get(tx: &mut Tx<'_>, tid: &str, id: &str, version: i64) -> Result<Option<ElementRevision>> {
+        let revision: Option<ElementRevision> = sqlx::query_as(
+            "SELECT * FROM element_revisions WHERE tid = $1 AND id = $2 AND version = $3")
+            .bind(tid)
+            .bind(id)
+            .bind(version)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(revision)
+    }
+
+    /// Get the element as it was at the time passed, from its current
+    /// version or a revision, or `None` if it didn't exist or was in the
+    /// trash at that time.
+    ///
+    /// Moving the element to the trash and restoring it don't change its
+    /// `updated_at`, so the version current at that time is the oldest one
+    /// updated before and not replaced yet.
+    #[instrument(name = "ElementRevision::element_at", skip_all, fields(tid = %tid))]
+    pub async fn element_at(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        id: &str,
+        at: NaiveDateTime,
+    ) -> Result<Option<Element>> {
+        Tenant::exists_or_fail(tx, tid).await?;
+        let element: Option<Element> = sqlx::query_as(
+                r#"
+            SELECT tid, id, data, created_at, updated_at, updated_by, version, deleted_at
+            FROM elements
+            WHERE tid = $1 AND id = $2 AND updated_at <= $3
+            UNION ALL
+            SELECT tid, id, data, created_at, updated_at, updated_by, version, deleted_at
+            FROM element_revisions
+            WHERE tid = $1 AND id = $2 AND updated_at <= $3 AND replaced_at > $3
+            ORDER BY version
+            LIMIT 1
+                "#
+            )
+            .bind(tid)
+            .bind(id)
+            .bind(at)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(element.filter(|el| el.deleted_at.is_none()))
+    }
+
+    /// Rewrite the element with the data of the revision `version`, as a new
+    /// version, even if the element was deleted, or `None` if not found.
+    #[instrument(name = "ElementRevision::restore", skip_all, fields(tid = %tid))]
+    pub async fn restore(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        id: &str,
+        version: i64,
+        by: Option<&str>,
+    ) -> Result<Option<Element>> {
+        Tenant::exists_or_fail(tx, tid).await?;
+        let Some(revision) = Self::get(tx, tid, id, version).await? else {
+            return Ok(None);
+        };
+        let el_form = ElementPayload { id: None, data: revision.element.data };
+        Element::save(tx, tid, id, el_form, by).await.map(Some)
+    }
+}
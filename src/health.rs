@@ -1,9 +1,23 @@
+use actix_contrib_rest::app_state::AppState;
+use actix_contrib_rest::result::{AppError, Result};
+use actix_web::rt::time::timeout;
+use actix_web::web::Data;
 use actix_web::{get, HttpResponse, Responder};
+use actix_web_validator::Query;
+use log::error;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use sqlx::Connection;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use validator::Validate;
 
 use crate::BACKSET_VERSION;
 
+/// Max time to wait for the DB check if the timeout is not provided.
+pub const DEFAULT_DB_TIMEOUT_MILLIS: u64 = 5000;
+
 static HEALTH_CHECK: LazyLock<String> = LazyLock::new(|| {
     json!({
         "status": "UP",
@@ -12,9 +26,134 @@ static HEALTH_CHECK: LazyLock<String> = LazyLock::new(|| {
     }).to_string()
 });
 
+static NOT_READY: LazyLock<String> = LazyLock::new(|| {
+    json!({
+        "status": "DOWN",
+        "service": "backset",
+        "version": BACKSET_VERSION,
+    }).to_string()
+});
+
+/// Whether the server accepts new requests, it turns not ready when
+/// the server is shutting down, so load balancers stop sending requests
+/// while the requests in progress are completed.
+#[derive(Debug)]
+pub struct Readiness(AtomicBool);
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Readiness(AtomicBool::new(true))
+    }
+}
+
+impl Readiness {
+    pub fn is_ready(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn set_not_ready(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct HealthQuery {
+    /// Whether to check the DB connection as well.
+    #[serde(default)]
+    pub db: bool,
+    /// Max time to wait for the DB check, in milliseconds.
+    #[validate(range(min = 1, max = 60000))]
+    pub timeout: Option<u64>,
+}
+
+/// Result of the DB connection check.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DbHealth {
+    pub status: String,
+    /// Time taken by the check, in milliseconds.
+    pub latency_ms: Option<f64>,
+    /// Connections opened in the pool, and how many of them are idle.
+    pub pool_size: Option<u32>,
+    pub pool_idle: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Check the connection with the DB executing a `SELECT 1`, with the pool
+/// if initialized or otherwise with a new connection, failing if it
+/// takes longer than `max_time`. It returns the time taken.
+pub async fn check_conn(state: &AppState, max_time: Option<Duration>) -> Result<Duration> {
+    let max_time = max_time.unwrap_or(Duration::from_millis(DEFAULT_DB_TIMEOUT_MILLIS));
+    let start = Instant::now();
+    let check = async {
+        match state.pool.as_ref() {
+            Some(pool) => sqlx::query("SELECT 1").execute(pool).await.map_err(AppError::DB),
+            None => {
+                let mut conn = state.get_conn().await?;
+                let res = sqlx::query("SELECT 1").execute(&mut conn).await.map_err(AppError::DB);
+                conn.close().await.ok();
+                res
+            }
+        }
+    };
+    timeout(max_time, check)
+        .await
+        .map_err(|_| AppError::Unexpected(anyhow::anyhow!(
+            "timeout of {}ms exceeded", max_time.as_millis()
+        )))??;
+    Ok(start.elapsed())
+}
+
 #[get("")]
-pub async fn health_check_handler() -> impl Responder {
+pub async fn health_check_handler(app: Data<AppState>, query: Query<HealthQuery>) -> impl Responder {
+    if !query.db {
+        return HttpResponse::Ok()
+            .content_type("application/json")
+            .body(HEALTH_CHECK.as_str());
+    }
+    let res = check_conn(&app, query.timeout.map(Duration::from_millis)).await;
+    if let Err(e) = res.as_ref() {
+        // The endpoint is public, so the error is only logged, it may have details of the DB
+        error!("DB health check failed: {e}");
+    }
+    let pool = app.pool.as_ref();
+    let db = DbHealth {
+        status: if res.is_ok() { "UP" } else { "DOWN" }.to_string(),
+        latency_ms: res.as_ref().ok().map(|d| d.as_secs_f64() * 1000.0),
+        pool_size: pool.map(|p| p.size()),
+        pool_idle: pool.map(|p| p.num_idle()),
+        error: res.as_ref().err().map(|_| "DB connection check failed".to_string()),
+    };
+    let body = json!({
+        "status": db.status,
+        "service": "backset",
+        "version": BACKSET_VERSION,
+        "db": db,
+    });
+    match res {
+        Ok(_) => HttpResponse::Ok().json(body),
+        Err(_) => HttpResponse::ServiceUnavailable().json(body),
+    }
+}
+
+/// Liveness probe, the server is running.
+#[get("/live")]
+pub async fn live_handler() -> impl Responder {
     HttpResponse::Ok()
         .content_type("application/json")
         .body(HEALTH_CHECK.as_str())
 }
+
+/// Readiness probe, the server accepts new requests. If the [`Readiness`]
+/// state is not registered in the app, the server is always ready.
+#[get("/ready")]
+pub async fn ready_handler(readiness: Option<Data<Readiness>>) -> impl Responder {
+    match readiness {
+        Some(r) if !r.is_ready() => HttpResponse::ServiceUnavailable()
+            .content_type("application/json")
+            .body(NOT_READY.as_str()),
+        _ => HttpResponse::Ok()
+            .content_type("application/json")
+            .body(HEALTH_CHECK.as_str()),
+    }
+}
@@ -1,9 +1,18 @@
 pub mod app_args;
 pub mod app_cmd;
 pub mod app_server;
+pub mod auth;
+pub mod conditional;
+pub mod cursor;
 pub mod health;
+pub mod metrics;
+pub mod telemetry;
+pub mod trash;
 
 pub mod elements;
+pub mod relationships;
+pub mod schemas;
+pub mod sets;
 pub mod tenants;
 
 pub mod routes;
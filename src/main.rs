@@ -3,6 +3,8 @@
 use backset::app_args::{Args, Commands};
 use backset::app_cmd::AppCmd;
 use backset::app_server::AppServer;
+use backset::telemetry;
+use backset::telemetry::TraceExporter;
 use backset::{BACKSET_PORT, BACKSET_VERSION};
 use log::error;
 use server_env_config::Config;
@@ -20,9 +22,11 @@ async fn main() -> anyhow::Result<()> {
             // HTTP REST Server
             let app = AppServer::build(config, BACKSET_VERSION).await?;
             app.server.await?;
+            telemetry::shutdown(app.tracer_provider);
         }
         _ => {
-            // Command-line tool
+            // Command-line tool, not traced
+            telemetry::init(TraceExporter::None).ok();
             let app = AppCmd::build(config).await.unwrap_or_else(|error| {
                 error!("{error}");
                 exit(1);
@@ -0,0 +1,108 @@
+//! Metrics of the server exposed in the Prometheus text format at "/metrics".
+
+use actix_contrib_rest::app_state::AppState;
+use actix_contrib_rest::result::{AppError, HttpResult};
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::web::Data;
+use actix_web::{get, Error, HttpResponse};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use std::sync::LazyLock;
+use std::time::Instant;
+
+/// Collectors of the metrics, registered in their own registry.
+pub struct Metrics {
+    pub registry: Registry,
+    /// Requests by method, route pattern (e.g. "/{tid}/{id}") and status.
+    pub http_requests: IntCounterVec,
+    /// Time taken by the requests by method and route pattern, in seconds.
+    pub http_duration: HistogramVec,
+    pub db_pool_size: IntGauge,
+    pub db_pool_idle: IntGauge,
+    /// Elements created and deleted by tenant.
+    pub elements_created: IntCounterVec,
+    pub elements_deleted: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new_custom(Some("backset".to_string()), None)?;
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests received"),
+            &["method", "path", "status"],
+        )?;
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP requests duration"),
+            &["method", "path"],
+        )?;
+        let db_pool_size = IntGauge::new("db_pool_size", "Connections opened in the DB pool")?;
+        let db_pool_idle = IntGauge::new("db_pool_idle", "Idle connections in the DB pool")?;
+        let elements_created = IntCounterVec::new(
+            Opts::new("elements_created_total", "Elements created"),
+            &["tenant"],
+        )?;
+        let elements_deleted = IntCounterVec::new(
+            Opts::new("elements_deleted_total", "Elements deleted"),
+            &["tenant"],
+        )?;
+        registry.register(Box::new(http_requests.clone()))?;
+        registry.register(Box::new(http_duration.clone()))?;
+        registry.register(Box::new(db_pool_size.clone()))?;
+        registry.register(Box::new(db_pool_idle.clone()))?;
+        registry.register(Box::new(elements_created.clone()))?;
+        registry.register(Box::new(elements_deleted.clone()))?;
+        Ok(Metrics {
+            registry,
+            http_requests,
+            http_duration,
+            db_pool_size,
+            db_pool_idle,
+            elements_created,
+            elements_deleted,
+        })
+    }
+}
+
+/// Metrics shared by all the workers of the server.
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(|| {
+    Metrics::new().expect("invalid metrics definition")
+});
+
+/// Middleware that records the count and duration of the requests. The path label
+/// is the pattern of the route matched, so tenant and element ids are not
+/// recorded, and "unmatched" for requests that don't match any route.
+pub async fn metrics_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> core::result::Result<ServiceResponse<impl MessageBody>, Error> {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    let res = next.call(req).await?;
+    let path = res.request().match_pattern().unwrap_or("unmatched".to_string());
+    let status = res.status().as_u16().to_string();
+    METRICS.http_requests
+        .with_label_values(&[method.as_str(), path.as_str(), status.as_str()])
+        .inc();
+    METRICS.http_duration
+        .with_label_values(&[method.as_str(), path.as_str()])
+        .observe(start.elapsed().as_secs_f64());
+    Ok(res)
+}
+
+#[get("")]
+pub async fn metrics_handler(app: Data<AppState>) -> HttpResult {
+    if let Some(pool) = app.pool.as_ref() {
+        METRICS.db_pool_size.set(pool.size() as i64);
+        METRICS.db_pool_idle.set(pool.num_idle() as i64);
+    }
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&METRICS.registry.gather(), &mut buffer)
+        .map_err(|e| AppError::Unexpected(e.into()))?;
+    Ok(HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(buffer))
+}
@@ -0,0 +1,134 @@
+use actix_contrib_rest::app_state::AppState;
+use actix_contrib_rest::page::Page;
+use actix_contrib_rest::query::QuerySearch;
+use actix_contrib_rest::result::{AppError, HttpResult};
+use actix_web::web::{Data, Path};
+use actix_web::{delete, get, post, HttpResponse};
+use actix_web_validator::{Json, Query};
+
+use crate::elements::model::Element;
+use crate::relationships::model::{
+    Relationship, RelationshipPayload, RelationshipQuery, TraverseMode, TraversePath, TraverseQuery,
+};
+use crate::tenants::model::Tenant;
+
+#[post("{tid}/{id}/relationships")]
+async fn create(
+    app: Data<AppState>,
+    path: Path<(String, String)>,
+    rel_form: Json<RelationshipPayload>,
+) -> HttpResult {
+    let mut tx = app.get_tx().await?;
+
+    let rel = Relationship::insert(
+        &mut tx,
+        path.as_ref().0.as_str(),
+        path.as_ref().1.as_str(),
+        rel_form.0,
+    ).await?;
+
+    app.commit_tx(tx).await?;
+    Ok(HttpResponse::Created().json(rel))
+}
+
+#[get("{tid}/{id}/relationships")]
+async fn list(
+    app: Data<AppState>,
+    path: Path<(String, String)>,
+    rel_query: Query<RelationshipQuery>,
+    query: Query<QuerySearch>,
+) -> HttpResult {
+    let (tid, id) = path.into_inner();
+    let mut tx = app.get_tx().await?;
+    Tenant::exists_or_fail(&mut tx, tid.as_str()).await?;
+    if !Element::exists(&mut tx, tid.as_str(), id.as_str(), false).await? {
+        return Err(AppError::ResourceNotFound {
+            resource: "element",
+            attribute: "id",
+            value: id,
+        });
+    }
+    let query = query.into_inner();
+    let total = if query.include_total.unwrap_or(true) {
+        Some(Relationship::count(&mut tx, tid.as_str(), id.as_str(), &rel_query).await?)
+    } else {
+        None
+    };
+    let rels = match total {
+        Some(0) => Page::empty(),
+        _ => {
+            let data = Relationship::find(
+                &mut tx, tid.as_str(), id.as_str(), &rel_query, &query
+            ).await?;
+            Page::with_data(data, total, query.offset)
+        }
+    };
+    app.commit_tx(tx).await?;
+    Ok(HttpResponse::Ok().json(rels))
+}
+
+#[delete("{tid}/{id}/relationships/{rel_type}/{to_id}")]
+async fn delete(
+    app: Data<AppState>,
+    path: Path<(String, String, String, String)>,
+) -> HttpResult {
+    let (tid, from_id, rel_type, to_id) = path.into_inner();
+    let mut tx = app.get_tx().await?;
+
+    let rows_deleted = Relationship::delete(
+        &mut tx,
+        tid.as_str(),
+        from_id.as_str(),
+        rel_type.as_str(),
+        to_id.as_str(),
+    ).await?;
+
+    app.commit_tx(tx).await?;
+    match rows_deleted {
+        0 => Ok(HttpResponse::NotFound().finish()),
+        _ => Ok(HttpResponse::NoContent().finish()),
+    }
+}
+
+#[get("{tid}/{id}/traverse")]
+async fn traverse(
+    app: Data<AppState>,
+    path: Path<(String, String)>,
+    query: Query<TraverseQuery>,
+) -> HttpResult {
+    let (tid, id) = path.into_inner();
+    let mut tx = app.get_tx().await?;
+    Tenant::exists_or_fail(&mut tx, tid.as_str()).await?;
+    if !Element::exists(&mut tx, tid.as_str(), id.as_str(), false).await? {
+        return Err(AppError::ResourceNotFound {
+            resource: "element",
+            attribute: "id",
+            value: id,
+        });
+    }
+    let resp = match query.mode.unwrap_or_default() {
+        TraverseMode::Reachable => {
+            let data = Relationship::find_reachable(
+                &mut tx, tid.as_str(), id.as_str(), &query
+            ).await?;
+            HttpResponse::Ok().json(Page::with_data(data, None, 0))
+        }
+        TraverseMode::Path => {
+            let to_id = query.to.as_deref().ok_or(AppError::StaticValidation(
+                "argument \"to\" is required in \"path\" mode"
+            ))?;
+            let path = Relationship::find_path(
+                &mut tx, tid.as_str(), id.as_str(), to_id, &query
+            ).await?;
+            HttpResponse::Ok().json(TraversePath { path })
+        }
+        TraverseMode::Cycle => {
+            let path = Relationship::find_path(
+                &mut tx, tid.as_str(), id.as_str(), id.as_str(), &query
+            ).await?;
+            HttpResponse::Ok().json(TraversePath { path })
+        }
+    };
+    app.commit_tx(tx).await?;
+    Ok(resp)
+}
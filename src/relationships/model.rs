@@ -0,0 +1,507 @@
+use actix_contrib_rest::db::Tx;
+use actix_contrib_rest::query::QuerySearch;
+use actix_contrib_rest::result::{AppError, Result};
+use chrono::NaiveDateTime;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::postgres::PgQueryResult;
+use sqlx::types::Json;
+use std::sync::LazyLock;
+use tracing::instrument;
+use validator::Validate;
+
+use crate::elements::model::Element;
+use crate::tenants::model::Tenant;
+use crate::PAGE_SIZE;
+
+static REL_TYPE_VALID: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?i)[a-z0-9][a-z0-9_\-\.\:]*$").unwrap()
+});
+
+/// A typed edge from one element to another within the same tenant.
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct Relationship {
+    #[serde(skip_serializing, default)]
+    pub tid: String,
+    pub from_id: String,
+    pub rel_type: String,
+    pub to_id: String,
+    pub data: Json<Map<String, Value>>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Serialize, Validate)]
+pub struct RelationshipPayload {
+    #[validate(length(min = 1, max = 80))]
+    #[validate(regex(
+        path = *REL_TYPE_VALID,
+        code = "invalid_rel_type",
+        message = "rel_type can only contains letters, numbers or the symbols _-.: \
+        and must starts with a letter or number"
+    ))]
+    pub rel_type: String,
+    #[validate(length(min = 1, max = 256))]
+    pub to_id: String,
+    pub data: Option<Json<Map<String, Value>>>,
+}
+
+/// Direction of the relationships of an element: the ones that start
+/// from the element (`out`), or the ones that point to it (`in`).
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    #[default]
+    Out,
+    In,
+}
+
+impl Direction {
+    /// Column that has to match the id of the element.
+    fn column(&self) -> &'static str {
+        match self {
+            Direction::Out => "from_id",
+            Direction::In => "to_id",
+        }
+    }
+
+    /// Column with the id of the element at the other
+    /// side of the relationship.
+    fn other_column(&self) -> &'static str {
+        match self {
+            Direction::Out => "to_id",
+            Direction::In => "from_id",
+        }
+    }
+
+    /// SQL condition over the relationships `r` that excludes the
+    /// ones with the element at the other side in the trash.
+    fn live_other_sql(&self) -> String {
+        format!(
+            "EXISTS(SELECT id FROM elements e WHERE e.tid = r.tid AND e.id = r.{} AND e.deleted_at IS NULL)",
+            self.other_column()
+        )
+    }
+}
+
+/// Query arguments to filter the relationships of an element.
+#[derive(Debug, Deserialize, Validate)]
+pub struct RelationshipQuery {
+    pub direction: Option<Direction>,
+    #[validate(length(min = 1, max = 80))]
+    pub rel_type: Option<String>,
+}
+
+/// Max depth allowed when traversing relationships.
+pub const MAX_TRAVERSE_DEPTH: i32 = 10;
+
+/// Max number of steps visited in a traversal, no matter the depth,
+/// so a single request cannot scan a whole tenant.
+pub const MAX_TRAVERSE_SCAN: i64 = 10_000;
+
+/// What to compute when traversing the relationships from an element.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TraverseMode {
+    /// All the elements reachable from the element.
+    #[default]
+    Reachable,
+    /// Shortest path from the element to another element.
+    Path,
+    /// Shortest cycle that starts and ends in the element.
+    Cycle,
+}
+
+/// Query arguments to traverse the relationships from an element.
+#[derive(Debug, Deserialize, Validate)]
+pub struct TraverseQuery {
+    pub mode: Option<TraverseMode>,
+    /// Target element, required in [`TraverseMode::Path`] mode.
+    #[validate(length(min = 1, max = 256))]
+    pub to: Option<String>,
+    #[validate(length(min = 1, max = 80))]
+    pub rel_type: Option<String>,
+    pub direction: Option<Direction>,
+    #[validate(range(min = 1, max = "MAX_TRAVERSE_DEPTH"))]
+    pub max_depth: Option<i32>,
+    #[validate(range(min = 1, max = 1000))]
+    pub limit: Option<i64>,
+}
+
+impl TraverseQuery {
+    pub fn max_depth(&self) -> i32 {
+        self.max_depth.unwrap_or(3)
+    }
+
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(PAGE_SIZE)
+    }
+}
+
+/// An element reached traversing relationships, and the
+/// minimum number of relationships followed to get there.
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct ReachedElement {
+    pub id: String,
+    pub depth: i32,
+}
+
+/// Ids of the elements in a path, from the starting element to the
+/// target element, or `None` if there is no path between them.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TraversePath {
+    pub path: Option<Vec<String>>,
+}
+
+/// Recursive CTEs `walk`, that follows the relationships from the element `$2`
+/// up to the depth `$4`, optionally filtered by the type `$3`, and `scan`,
+/// that limits the rows `walk` can visit. Elements in the trash are not
+/// followed. Each row has the `id` of
+/// the element reached, the `depth`, and the `prev` element in the path.
+fn walk_sql(direction: Direction) -> String {
+    format!(
+        r#"
+        WITH RECURSIVE walk(id, depth, prev) AS (
+            SELECT $2::VARCHAR, 0, NULL::VARCHAR
+          UNION
+            SELECT r.{next}, w.depth + 1, w.id
+              FROM walk w
+              JOIN relationships r ON r.tid = $1 AND r.{col} = w.id
+              JOIN elements e ON e.tid = $1 AND e.id = r.{next} AND e.deleted_at IS NULL
+              WHERE w.depth < $4 AND ($3::VARCHAR IS NULL OR r.rel_type = $3)
+        ), scan AS (
+            SELECT * FROM walk LIMIT {MAX_TRAVERSE_SCAN}
+        )
+        "#,
+        col = direction.column(),
+        next = direction.other_column(),
+    )
+}
+
+impl Relationship {
+    #[instrument(name = "Relationship::insert", skip_all, fields(tid = %tid))]
+    pub async fn insert(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        from_id: &str,
+        rel_form: RelationshipPayload,
+    ) -> Result<Relationship> {
+        Tenant::exists_or_fail(tx, tid).await?;
+        for id in [from_id, rel_form.to_id.as_str()] {
+            if !Element::exists(tx, tid, id, false).await? {
+                return Err(AppError::ResourceNotFound {
+                    resource: "element",
+                    attribute: "id",
+                    value: id.to_string(),
+                });
+            }
+        }
+        let exists = Self::exists(
+            tx, tid, from_id, rel_form.rel_type.as_str(), rel_form.to_id.as_str()
+        ).await?;
+        if exists {
+            return Err(AppError::Validation(
+                Some("already_exists"),
+                format!(
+                    "relationship \"{}\" from \"{from_id}\" to \"{}\" already exists",
+                    rel_form.rel_type, rel_form.to_id
+                )
+            ));
+        }
+        let rel = sqlx::query_as::<_, Relationship>(
+            "INSERT INTO relationships (tid, from_id, rel_type, to_id, data, created_at) \
+            VALUES ($1, $2, $3, $4, $5, NOW()) RETURNING *",
+            )
+            .bind(tid)
+            .bind(from_id)
+            .bind(rel_form.rel_type.as_str())
+            .bind(rel_form.to_id.as_str())
+            .bind(rel_form.data.unwrap_or_default())
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(rel)
+    }
+
+    #[instrument(name = "Relationship::exists", skip_all, fields(tid = %tid))]
+    pub async fn exists(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        from_id: &str,
+        rel_type: &str,
+        to_id: &str,
+    ) -> Result<bool> {
+        let res: (bool,) = sqlx::query_as(
+                r#"
+            SELECT EXISTS(
+              SELECT from_id FROM relationships
+                WHERE tid = $1 AND from_id = $2 AND rel_type = $3 AND to_id = $4
+            )
+                "#
+            )
+            .bind(tid)
+            .bind(from_id)
+            .bind(rel_type)
+            .bind(to_id)
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(res.0)
+    }
+
+    #[instrument(name = "Relationship::delete", skip_all, fields(tid = %tid))]
+    pub async fn delete(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        from_id: &str,
+        rel_type: &str,
+        to_id: &str,
+    ) -> Result<u64> {
+        Tenant::exists_or_fail(tx, tid).await?;
+        let res: PgQueryResult = sqlx::query(
+                r#"
+            DELETE FROM relationships
+              WHERE tid = $1 AND from_id = $2 AND rel_type = $3 AND to_id = $4
+                "#
+            )
+            .bind(tid)
+            .bind(from_id)
+            .bind(rel_type)
+            .bind(to_id)
+            .execute(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(res.rows_affected())
+    }
+
+    /// Delete all the relationships from or to the element.
+    #[instrument(name = "Relationship::delete_all", skip_all, fields(tid = %tid))]
+    pub async fn delete_all(tx: &mut Tx<'_>, tid: &str, id: &str) -> Result<u64> {
+        let res: PgQueryResult = sqlx::query(
+                "DELETE FROM relationships WHERE tid = $1 AND (from_id = $2 OR to_id = $2)")
+            .bind(tid)
+            .bind(id)
+            .execute(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(res.rows_affected())
+    }
+
+    #[instrument(name = "Relationship::count", skip_all, fields(tid = %tid))]
+    pub async fn count(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        id: &str,
+        rel_query: &RelationshipQuery,
+    ) -> Result<i64> {
+        let direction = rel_query.direction.unwrap_or_default();
+        let sql = format!(
+            r#"
+            SELECT COUNT(*)
+              FROM relationships r
+              WHERE tid = $1 AND {} = $2 AND ($3::VARCHAR IS NULL OR rel_type = $3)
+                AND {}
+            "#,
+            direction.column(),
+            direction.live_other_sql(),
+        );
+        let count: (i64,) = sqlx::query_as(sql.as_str())
+            .bind(tid)
+            .bind(id)
+            .bind(rel_query.rel_type.as_deref())
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(count.0)
+    }
+
+    #[instrument(name = "Relationship::find", skip_all, fields(tid = %tid))]
+    pub async fn find(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        id: &str,
+        rel_query: &RelationshipQuery,
+        query: &QuerySearch,
+    ) -> Result<Vec<Relationship>> {
+        let direction = rel_query.direction.unwrap_or_default();
+        let sql = format!(
+            r#"
+            SELECT *
+            FROM relationships r
+            WHERE tid = $1 AND {} = $2 AND ($3::VARCHAR IS NULL OR rel_type = $3)
+              AND {}
+            ORDER BY created_at DESC, rel_type, from_id, to_id
+            LIMIT $4 OFFSET $5
+            "#,
+            direction.column(),
+            direction.live_other_sql(),
+        );
+        let rels: Vec<Relationship> = sqlx::query_as(sql.as_str())
+            .bind(tid)
+            .bind(id)
+            .bind(rel_query.rel_type.as_deref())
+            .bind(query.page_size)
+            .bind(query.offset)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(rels)
+    }
+
+    /// Find the relationships of all the elements sorted by `from_id`, `rel_type` and
+    /// `to_id`, only the ones after `after` if passed, so all the relationships of a
+    /// tenant can be read in batches, except the ones of elements in the trash.
+    #[instrument(name = "Relationship::find_by_id", skip_all, fields(tid = %tid))]
+    pub async fn find_by_id(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        after: Option<(&str, &str, &str)>,
+        limit: i64,
+    ) -> Result<Vec<Relationship>> {
+        let (after_from, after_type, after_to) = match after {
+            Some((from_id, rel_type, to_id)) => (Some(from_id), Some(rel_type), Some(to_id)),
+            None => (None, None, None),
+        };
+        let sql = format!(
+            r#"
+            SELECT *
+            FROM relationships r
+            WHERE tid = $1 AND ($2::VARCHAR IS NULL OR (from_id, rel_type, to_id) > ($2, $3, $4))
+              AND {} AND {}
+            ORDER BY from_id, rel_type, to_id
+            LIMIT $5
+            "#,
+            Direction::In.live_other_sql(),
+            Direction::Out.live_other_sql(),
+        );
+        let rels: Vec<Relationship> = sqlx::query_as(sql.as_str())
+            .bind(tid)
+            .bind(after_from)
+            .bind(after_type)
+            .bind(after_to)
+            .bind(limit)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(rels)
+    }
+
+    /// Insert the relationships as they are, keeping their creation time.
+    #[instrument(name = "Relationship::insert_records", skip_all, fields(tid = %tid))]
+    pub async fn insert_records(tx: &mut Tx<'_>, tid: &str, rels: Vec<Relationship>) -> Result<u64> {
+        let mut from_ids: Vec<String> = Vec::with_capacity(rels.len());
+        let mut rel_types: Vec<String> = Vec::with_capacity(rels.len());
+        let mut to_ids: Vec<String> = Vec::with_capacity(rels.len());
+        let mut data: Vec<Json<Map<String, Value>>> = Vec::with_capacity(rels.len());
+        let mut created_at: Vec<NaiveDateTime> = Vec::with_capacity(rels.len());
+        for rel in rels {
+            from_ids.push(rel.from_id);
+            rel_types.push(rel.rel_type);
+            to_ids.push(rel.to_id);
+            data.push(rel.data);
+            created_at.push(rel.created_at);
+        }
+        let res: PgQueryResult = sqlx::query(
+                r#"
+            INSERT INTO relationships (tid, from_id, rel_type, to_id, data, created_at)
+            SELECT $1, from_id, rel_type, to_id, data, created_at
+            FROM UNNEST($2::VARCHAR[], $3::VARCHAR[], $4::VARCHAR[], $5::JSONB[], $6::TIMESTAMP[])
+              AS r(from_id, rel_type, to_id, data, created_at)
+                "#
+            )
+            .bind(tid)
+            .bind(from_ids)
+            .bind(rel_types)
+            .bind(to_ids)
+            .bind(data)
+            .bind(created_at)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| match e.as_database_error() {
+                Some(db_error) if db_error.is_unique_violation() => AppError::StaticValidation(
+                    "relationships duplicated"
+                ),
+                Some(db_error) if db_error.is_foreign_key_violation() => AppError::StaticValidation(
+                    "elements of relationships not found"
+                ),
+                _ => AppError::DB(e),
+            })?;
+        Ok(res.rows_affected())
+    }
+
+    /// Find the elements reachable from the element `id`
+    /// sorted by depth, the closest first.
+    #[instrument(name = "Relationship::find_reachable", skip_all, fields(tid = %tid))]
+    pub async fn find_reachable(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        id: &str,
+        query: &TraverseQuery,
+    ) -> Result<Vec<ReachedElement>> {
+        let sql = format!(
+            r#"
+            {}
+            SELECT id, MIN(depth) AS depth
+              FROM scan
+              WHERE id <> $2
+              GROUP BY id
+              ORDER BY depth, id
+              LIMIT $5
+            "#,
+            walk_sql(query.direction.unwrap_or_default())
+        );
+        let elements: Vec<ReachedElement> = sqlx::query_as(sql.as_str())
+            .bind(tid)
+            .bind(id)
+            .bind(query.rel_type.as_deref())
+            .bind(query.max_depth())
+            .bind(query.limit())
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(elements)
+    }
+
+    /// Find the shortest path from the element `id` to the element `to_id`,
+    /// including both. If `id` and `to_id` are the same element, the path
+    /// found is the shortest cycle from the element.
+    #[instrument(name = "Relationship::find_path", skip_all, fields(tid = %tid))]
+    pub async fn find_path(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        id: &str,
+        to_id: &str,
+        query: &TraverseQuery,
+    ) -> Result<Option<Vec<String>>> {
+        let sql = format!(
+            r#"
+            {}, back(id, prev, depth) AS (
+                (SELECT id, prev, depth FROM scan
+                   WHERE id = $5 AND depth > 0 ORDER BY depth LIMIT 1)
+              UNION ALL
+                SELECT s.id, s.prev, s.depth FROM back b
+                  JOIN LATERAL (
+                    SELECT id, prev, depth FROM scan
+                      WHERE id = b.prev AND depth = b.depth - 1
+                      ORDER BY prev NULLS FIRST LIMIT 1
+                  ) s ON true
+            )
+            SELECT id FROM back ORDER BY depth
+            "#,
+            walk_sql(query.direction.unwrap_or_default())
+        );
+        let path: Vec<(String,)> = sqlx::query_as(sql.as_str())
+            .bind(tid)
+            .bind(id)
+            .bind(query.rel_type.as_deref())
+            .bind(query.max_depth())
+            .bind(to_id)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        match path.len() {
+            0 => Ok(None),
+            _ => Ok(Some(path.into_iter().map(|r| r.0).collect())),
+        }
+    }
+}
@@ -1,31 +1,107 @@
 use crate::elements::api::{
+    bulk as elements_bulk,
     create as elements_create,
     delete as elements_delete,
     list as elements_list,
+    mget as elements_mget,
+    patch as elements_patch,
     read as elements_read,
     put as elements_put,
+    restore as elements_restore,
+    restore_revision as elements_restore_revision,
+    revisions as elements_revisions,
 };
-use crate::health::health_check_handler;
-use crate::tenants::api::{create, delete, list, read, put};
+use crate::elements::bulk::MAX_BULK_SIZE;
+use crate::health::{health_check_handler, live_handler, ready_handler};
+use crate::metrics::metrics_handler;
+use crate::relationships::api::{
+    create as relationships_create,
+    delete as relationships_delete,
+    list as relationships_list,
+    traverse as relationships_traverse,
+};
+use crate::schemas::api::{
+    delete as schemas_delete,
+    delete_set as schemas_delete_set,
+    put as schemas_put,
+    put_set as schemas_put_set,
+    read as schemas_read,
+    read_set as schemas_read_set,
+};
+use crate::sets::api::{
+    add_elements as sets_add_elements,
+    create as sets_create,
+    delete as sets_delete,
+    list as sets_list,
+    list_elements as sets_list_elements,
+    operation as sets_operation,
+    read as sets_read,
+    put as sets_put,
+    remove_element as sets_remove_element,
+};
+use crate::tenants::api::{create, delete, export, import, list, read, put, restore};
 use actix_web::web;
+use actix_web::web::PayloadConfig;
 
 pub fn config(conf: &mut web::ServiceConfig) {
-    let scope = web::scope("/health").service(health_check_handler);
+    let scope = web::scope("/health")
+        .service(health_check_handler)
+        .service(live_handler)
+        .service(ready_handler);
+    conf.service(scope);
+
+    let scope = web::scope("/metrics").service(metrics_handler);
     conf.service(scope);
 
     let scope = web::scope("/tenants")
         .service(create)
         .service(delete)
+        .service(export)
+        .service(import)
         .service(list)
         .service(read)
-        .service(put);
+        .service(put)
+        .service(restore)
+        .service(schemas_delete)
+        .service(schemas_put)
+        .service(schemas_read);
     conf.service(scope);
 
-    // "/{tenant}" and "/{tenant}/{id}"
+    // "/{tenant}/sets..." are registered before the elements
+    // endpoints, otherwise "sets" would match as an element id
     let scope = web::scope("")
+        .service(sets_add_elements)
+        .service(sets_create)
+        .service(sets_delete)
+        .service(sets_list)
+        .service(sets_list_elements)
+        .service(sets_operation)
+        .service(sets_read)
+        .service(sets_put)
+        .service(sets_remove_element)
+        .service(schemas_delete_set)
+        .service(schemas_put_set)
+        .service(schemas_read_set)
+        // "/{tenant}/{id}/relationships..."
+        .service(relationships_create)
+        .service(relationships_delete)
+        .service(relationships_list)
+        .service(relationships_traverse)
+        // "/{tenant}/{id}/restore" and "/{tenant}/{id}/revisions..."
+        .service(elements_restore)
+        .service(elements_restore_revision)
+        .service(elements_revisions)
+        // "/{tenant}" and "/{tenant}/{id}"
+        .service(
+            web::resource("{tid}/_bulk")
+                .app_data(PayloadConfig::new(MAX_BULK_SIZE))
+                .route(web::post().to(elements_bulk))
+        )
         .service(elements_create)
         .service(elements_delete)
         .service(elements_list)
+        .service(elements_mget)
+        .service(elements_patch)
         .service(elements_read)
         .service(elements_put);
     conf.service(scope);
@@ -0,0 +1,95 @@
+use actix_contrib_rest::app_state::AppState;
+use actix_contrib_rest::result::HttpResult;
+use actix_web::web::{Data, Json, Path};
+use actix_web::{delete, get, put, HttpResponse};
+use serde_json::Value;
+
+use crate::schemas::model::Schema;
+
+// "/tenants/{tid}/schema" endpoints, the schema of the whole tenant
+
+#[get("{tid}/schema")]
+async fn read(app: Data<AppState>, tid: Path<String>) -> HttpResult {
+    let mut tx = app.get_tx().await?;
+
+    let schema = Schema::get(&mut tx, tid.as_str(), None).await?;
+
+    app.commit_tx(tx).await?;
+    match schema {
+        Some(s) => Ok(HttpResponse::Ok().json(s)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[put("{tid}/schema")]
+async fn put(app: Data<AppState>, tid: Path<String>, schema: Json<Value>) -> HttpResult {
+    let mut tx = app.get_tx().await?;
+
+    let schema = Schema::save(&mut tx, tid.as_str(), None, schema.into_inner()).await?;
+
+    app.commit_tx(tx).await?;
+    Ok(HttpResponse::Ok().json(schema))
+}
+
+#[delete("{tid}/schema")]
+async fn delete(app: Data<AppState>, tid: Path<String>) -> HttpResult {
+    let mut tx = app.get_tx().await?;
+
+    let rows_deleted = Schema::delete(&mut tx, tid.as_str(), None).await?;
+
+    app.commit_tx(tx).await?;
+    match rows_deleted {
+        0 => Ok(HttpResponse::NotFound().finish()),
+        _ => Ok(HttpResponse::NoContent().finish()),
+    }
+}
+
+// "/{tid}/sets/{id}/schema" endpoints, the schema of the elements of a set
+
+#[get("{tid}/sets/{id}/schema")]
+async fn read_set(app: Data<AppState>, path: Path<(String, String)>) -> HttpResult {
+    let (tid, sid) = path.into_inner();
+    let mut tx = app.get_tx().await?;
+
+    let schema = Schema::get(&mut tx, tid.as_str(), Some(sid.as_str())).await?;
+
+    app.commit_tx(tx).await?;
+    match schema {
+        Some(s) => Ok(HttpResponse::Ok().json(s)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[put("{tid}/sets/{id}/schema")]
+async fn put_set(
+    app: Data<AppState>,
+    path: Path<(String, String)>,
+    schema: Json<Value>,
+) -> HttpResult {
+    let (tid, sid) = path.into_inner();
+    let mut tx = app.get_tx().await?;
+
+    let schema = Schema::save(
+        &mut tx,
+        tid.as_str(),
+        Some(sid.as_str()),
+        schema.into_inner(),
+    ).await?;
+
+    app.commit_tx(tx).await?;
+    Ok(HttpResponse::Ok().json(schema))
+}
+
+#[delete("{tid}/sets/{id}/schema")]
+async fn delete_set(app: Data<AppState>, path: Path<(String, String)>) -> HttpResult {
+    let (tid, sid) = path.into_inner();
+    let mut tx = app.get_tx().await?;
+
+    let rows_deleted = Schema::delete(&mut tx, tid.as_str(), Some(sid.as_str())).await?;
+
+    app.commit_tx(tx).await?;
+    match rows_deleted {
+        0 => Ok(HttpResponse::NotFound().finish()),
+        _ => Ok(HttpResponse::NoContent().finish()),
+    }
+}
@@ -0,0 +1,236 @@
+use actix_contrib_rest::db::Tx;
+use actix_contrib_rest::result::{AppError, Result};
+use jsonschema::Validator;
+use serde_json::{Map, Value};
+use sqlx::postgres::PgQueryResult;
+use sqlx::types::Json;
+use tracing::instrument;
+
+use crate::sets::model::Set;
+use crate::tenants::model::Tenant;
+
+/// Number of elements read at once to validate all the elements.
+const VALIDATE_BATCH_SIZE: i64 = 500;
+
+/// JSON Schema documents used to validate the attributes of the elements,
+/// registered for a whole tenant, or for the elements of a set
+/// (`sid` is the set id, or `None` for the tenant schema).
+pub struct Schema;
+
+/// Compile the schema, failing if it's not a valid JSON Schema document.
+fn compile(schema: &Value) -> Result<Validator> {
+    jsonschema::validator_for(schema).map_err(|e| {
+        AppError::Validation(Some("invalid_schema"), format!("invalid schema: {e}"))
+    })
+}
+
+/// Validate the attributes of an element with the schema passed, the
+/// error returned includes the JSON pointer of the attribute that failed.
+///
+/// ```
+/// use backset::schemas::model::validate_data;
+/// use serde_json::json;
+/// let schema = json!({ "properties": { "size": { "type": "integer", "minimum": 1 } } });
+/// let data = json!({ "size": 10 });
+/// assert!(validate_data(&schema, data.as_object().unwrap()).is_ok());
+/// let data = json!({ "size": 0 });
+/// assert_eq!(
+///     validate_data(&schema, data.as_object().unwrap()).unwrap_err().to_string(),
+///     "invalid attribute \"/size\": 0 is less than the minimum of 1",
+/// );
+/// ```
+pub fn validate_data(schema: &Value, data: &Map<String, Value>) -> Result<()> {
+    check(&compile(schema)?, data)
+}
+
+fn check(validator: &Validator, data: &Map<String, Value>) -> Result<()> {
+    let instance = Value::Object(data.clone());
+    match validator.iter_errors(&instance).next() {
+        None => Ok(()),
+        Some(error) => {
+            let pointer = error.instance_path.as_str();
+            let message = match pointer {
+                "" => format!("invalid element: {error}"),
+                _ => format!("invalid attribute \"{pointer}\": {error}"),
+            };
+            Err(AppError::Validation(Some("schema_validation"), message))
+        }
+    }
+}
+
+/// Check the element with the id in the error message, if any.
+fn check_element(validator: &Validator, id: &str, data: &Map<String, Value>) -> Result<()> {
+    check(validator, data).map_err(|e| match e {
+        AppError::Validation(code, error) => {
+            AppError::Validation(code, format!("element \"{id}\": {error}"))
+        }
+        e => e,
+    })
+}
+
+impl Schema {
+    #[instrument(name = "Schema::get", skip_all, fields(tid = %tid))]
+    pub async fn get(tx: &mut Tx<'_>, tid: &str, sid: Option<&str>) -> Result<Option<Value>> {
+        let schema: Option<(Json<Value>,)> = sqlx::query_as(
+                "SELECT schema FROM schemas WHERE tid = $1 AND sid IS NOT DISTINCT FROM $2"
+            )
+            .bind(tid)
+            .bind(sid)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(schema.map(|s| s.0.0))
+    }
+
+    /// Register the schema of the tenant, or of the set if `sid` is passed,
+    /// replacing the existing one if any. Existing elements are not validated.
+    #[instrument(name = "Schema::save", skip_all, fields(tid = %tid))]
+    pub async fn save(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        sid: Option<&str>,
+        schema: Value,
+    ) -> Result<Value> {
+        compile(&schema)?;
+        Tenant::exists_or_fail(tx, tid).await?;
+        if let Some(sid) = sid {
+            Set::exists_or_fail(tx, tid, sid).await?;
+        }
+        Self::delete(tx, tid, sid).await?;
+        let schema: (Json<Value>,) = sqlx::query_as(
+                r#"
+            INSERT INTO schemas (tid, sid, schema, created_at)
+              VALUES ($1, $2, $3, NOW())
+              RETURNING schema
+                "#
+            )
+            .bind(tid)
+            .bind(sid)
+            .bind(Json(schema))
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(schema.0.0)
+    }
+
+    /// Find the schemas of the tenant and its sets, as `(sid, schema)`,
+    /// the one of the tenant first.
+    #[instrument(name = "Schema::find_all", skip_all, fields(tid = %tid))]
+    pub async fn find_all(tx: &mut Tx<'_>, tid: &str) -> Result<Vec<(Option<String>, Value)>> {
+        let schemas: Vec<(Option<String>, Json<Value>)> = sqlx::query_as(
+                "SELECT sid, schema FROM schemas WHERE tid = $1 ORDER BY sid NULLS FIRST"
+            )
+            .bind(tid)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(schemas.into_iter().map(|(sid, schema)| (sid, schema.0)).collect())
+    }
+
+    #[instrument(name = "Schema::delete", skip_all, fields(tid = %tid))]
+    pub async fn delete(tx: &mut Tx<'_>, tid: &str, sid: Option<&str>) -> Result<u64> {
+        let res: PgQueryResult = sqlx::query(
+                "DELETE FROM schemas WHERE tid = $1 AND sid IS NOT DISTINCT FROM $2"
+            )
+            .bind(tid)
+            .bind(sid)
+            .execute(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(res.rows_affected())
+    }
+
+    /// Validate the attributes of an element with the schema of the tenant,
+    /// and the schemas of the sets the element belongs to, if any.
+    #[instrument(name = "Schema::validate_element", skip_all, fields(tid = %tid))]
+    pub async fn validate_element(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        id: Option<&str>,
+        data: &Map<String, Value>,
+    ) -> Result<()> {
+        let schemas: Vec<(Json<Value>,)> = sqlx::query_as(
+                r#"
+            SELECT schema FROM schemas
+              WHERE tid = $1 AND (
+                sid IS NULL OR
+                sid IN (SELECT sid FROM sets_elements WHERE tid = $1 AND eid = $2)
+              )
+              ORDER BY sid NULLS FIRST
+                "#
+            )
+            .bind(tid)
+            .bind(id)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        for (schema,) in schemas.iter() {
+            validate_data(schema, data)?;
+        }
+        Ok(())
+    }
+
+    /// Validate the elements to be added to a set with the schema of the set, if any.
+    #[instrument(name = "Schema::validate_members", skip_all, fields(tid = %tid))]
+    pub async fn validate_members(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        sid: &str,
+        ids: &[String],
+    ) -> Result<()> {
+        let Some(schema) = Self::get(tx, tid, Some(sid)).await? else {
+            return Ok(());
+        };
+        let validator = compile(&schema)?;
+        let elements: Vec<(String, Json<Map<String, Value>>)> = sqlx::query_as(
+                "SELECT id, data FROM elements WHERE tid = $1 AND id = ANY($2) ORDER BY id"
+            )
+            .bind(tid)
+            .bind(ids)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        for (id, data) in elements.iter() {
+            check_element(&validator, id, data)?;
+        }
+        Ok(())
+    }
+
+    /// Validate all the elements of the tenant not in the trash with the
+    /// schemas registered, e.g. once imported without validating them.
+    #[instrument(name = "Schema::validate_all", skip_all, fields(tid = %tid))]
+    pub async fn validate_all(tx: &mut Tx<'_>, tid: &str) -> Result<()> {
+        for (sid, schema) in Self::find_all(tx, tid).await? {
+            let validator = compile(&schema)?;
+            let mut after: Option<String> = None;
+            loop {
+                let elements: Vec<(String, Json<Map<String, Value>>)> = sqlx::query_as(
+                        r#"
+                    SELECT id, data FROM elements
+                      WHERE tid = $1 AND ($2::VARCHAR IS NULL OR id > $2) AND deleted_at IS NULL AND (
+                        $3::VARCHAR IS NULL OR
+                        id IN (SELECT eid FROM sets_elements WHERE tid = $1 AND sid = $3)
+                      )
+                      ORDER BY id
+                      LIMIT $4
+                        "#
+                    )
+                    .bind(tid)
+                    .bind(after.as_deref())
+                    .bind(sid.as_deref())
+                    .bind(VALIDATE_BATCH_SIZE)
+                    .fetch_all(&mut **tx)
+                    .await
+                    .map_err(AppError::DB)?;
+                let Some((last, _)) = elements.last() else {
+                    break;
+                };
+                after = Some(last.clone());
+                for (id, data) in elements.iter() {
+                    check_element(&validator, id, data)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
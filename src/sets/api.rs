@@ -0,0 +1,230 @@
+use actix_contrib_rest::app_state::AppState;
+use actix_contrib_rest::page::Page;
+use actix_contrib_rest::query::QuerySearch;
+use actix_contrib_rest::result::HttpResult;
+use actix_web::web::{Data, Path};
+use actix_web::{delete, get, post, put, HttpResponse};
+use actix_web_validator::{Json, Query};
+
+use crate::sets::model::{
+    AddedCount, Set, SetMembersPayload, SetOperationPayload, SetPayload, SetPayloadEdition,
+};
+use crate::tenants::model::Tenant;
+
+#[post("{tid}/sets")]
+async fn create(
+    app: Data<AppState>,
+    tid: Path<String>,
+    set_form: Json<SetPayload>,
+) -> HttpResult {
+    let mut tx = app.get_tx().await?;
+
+    let set = Set::insert(&mut tx, tid.as_str(), set_form.0).await?;
+
+    app.commit_tx(tx).await?;
+    Ok(HttpResponse::Created().json(set))
+}
+
+#[get("{tid}/sets/{id}")]
+async fn read(app: Data<AppState>, path: Path<(String, String)>) -> HttpResult {
+    let mut tx = app.get_tx().await?;
+
+    let set = Set::get(
+        &mut tx,
+        path.as_ref().0.as_str(),
+        path.as_ref().1.as_str()
+    ).await?;
+
+    app.commit_tx(tx).await?;
+    match set {
+        Some(s) => Ok(HttpResponse::Ok().json(s)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[get("{tid}/sets")]
+async fn list(
+    app: Data<AppState>,
+    tid: Path<String>,
+    query: Query<QuerySearch>
+) -> HttpResult {
+    let mut tx = app.get_tx().await?;
+    Tenant::exists_or_fail(&mut tx, tid.as_str()).await?;
+    let query = query.into_inner();
+    let total = if query.include_total.unwrap_or(true) {
+        Some(Set::count(&mut tx, tid.as_str(), query.q.as_deref()).await?)
+    } else {
+        None
+    };
+    let sets = match total {
+        Some(0) => Page::empty(),
+        _ => {
+            let data = Set::find(&mut tx, tid.as_str(), &query).await?;
+            Page::with_data(data, total, query.offset)
+        }
+    };
+    app.commit_tx(tx).await?;
+    Ok(HttpResponse::Ok().json(sets))
+}
+
+#[put("{tid}/sets/{id}")]
+async fn put(
+    app: Data<AppState>,
+    path: Path<(String, String)>,
+    set_form: Json<SetPayloadEdition>,
+) -> HttpResult {
+    let mut tx = app.get_tx().await?;
+
+    let set = Set::save(
+        &mut tx,
+        path.as_ref().0.as_str(),
+        path.as_ref().1.as_str(),
+        set_form.0
+    ).await?;
+
+    app.commit_tx(tx).await?;
+    Ok(HttpResponse::Ok().json(set))
+}
+
+#[delete("{tid}/sets/{id}")]
+async fn delete(app: Data<AppState>, path: Path<(String, String)>) -> HttpResult {
+    let mut tx = app.get_tx().await?;
+
+    let rows_deleted = Set::delete(
+        &mut tx,
+        path.as_ref().0.as_str(),
+        path.as_ref().1.as_str()
+    ).await?;
+
+    app.commit_tx(tx).await?;
+    match rows_deleted {
+        0 => Ok(HttpResponse::NotFound().finish()),
+        _ => Ok(HttpResponse::NoContent().finish()),
+    }
+}
+
+#[post("{tid}/sets/{id}/elements")]
+async fn add_elements(
+    app: Data<AppState>,
+    path: Path<(String, String)>,
+    members_form: Json<SetMembersPayload>,
+) -> HttpResult {
+    let mut tx = app.get_tx().await?;
+
+    let added = Set::add_elements(
+        &mut tx,
+        path.as_ref().0.as_str(),
+        path.as_ref().1.as_str(),
+        &members_form.ids,
+    ).await?;
+
+    app.commit_tx(tx).await?;
+    Ok(HttpResponse::Ok().json(AddedCount { added }))
+}
+
+#[get("{tid}/sets/{id}/elements")]
+async fn list_elements(
+    app: Data<AppState>,
+    path: Path<(String, String)>,
+    query: Query<QuerySearch>
+) -> HttpResult {
+    let (tid, id) = path.into_inner();
+    let mut tx = app.get_tx().await?;
+    Tenant::exists_or_fail(&mut tx, tid.as_str()).await?;
+    Set::exists_or_fail(&mut tx, tid.as_str(), id.as_str()).await?;
+    let query = query.into_inner();
+    let total = if query.include_total.unwrap_or(true) {
+        Some(Set::count_elements(&mut tx, tid.as_str(), id.as_str()).await?)
+    } else {
+        None
+    };
+    let ids = match total {
+        Some(0) => Page::empty(),
+        _ => {
+            let data = Set::find_elements(&mut tx, tid.as_str(), id.as_str(), &query).await?;
+            Page::with_data(data, total, query.offset)
+        }
+    };
+    app.commit_tx(tx).await?;
+    Ok(HttpResponse::Ok().json(ids))
+}
+
+#[delete("{tid}/sets/{id}/elements/{eid}")]
+async fn remove_element(
+    app: Data<AppState>,
+    path: Path<(String, String, String)>,
+) -> HttpResult {
+    let (tid, id, eid) = path.into_inner();
+    let mut tx = app.get_tx().await?;
+
+    let rows_deleted = Set::remove_element(
+        &mut tx,
+        tid.as_str(),
+        id.as_str(),
+        eid.as_str(),
+    ).await?;
+
+    app.commit_tx(tx).await?;
+    match rows_deleted {
+        0 => Ok(HttpResponse::NotFound().finish()),
+        _ => Ok(HttpResponse::NoContent().finish()),
+    }
+}
+
+#[post("{tid}/sets/_ops")]
+async fn operation(
+    app: Data<AppState>,
+    tid: Path<String>,
+    query: Query<QuerySearch>,
+    op_form: Json<SetOperationPayload>,
+) -> HttpResult {
+    let op_form = op_form.into_inner();
+    let sets = op_form.unique_sets();
+    let mut tx = app.get_tx().await?;
+    Tenant::exists_or_fail(&mut tx, tid.as_str()).await?;
+    Set::all_exist_or_fail(&mut tx, tid.as_str(), &sets).await?;
+
+    if let Some(set_form) = op_form.save_as {
+        let set = Set::insert_operation(
+            &mut tx,
+            tid.as_str(),
+            op_form.op,
+            &sets,
+            set_form,
+        ).await?;
+        app.commit_tx(tx).await?;
+        return Ok(HttpResponse::Created().json(set));
+    }
+
+    let query = query.into_inner();
+    let total = if query.include_total.unwrap_or(true) {
+        Some(Set::count_operation(&mut tx, tid.as_str(), op_form.op, &sets).await?)
+    } else {
+        None
+    };
+    let resp = if op_form.ids_only.unwrap_or(false) {
+        let ids = match total {
+            Some(0) => Page::empty(),
+            _ => {
+                let data = Set::find_operation_ids(
+                    &mut tx, tid.as_str(), op_form.op, &sets, &query
+                ).await?;
+                Page::with_data(data, total, query.offset)
+            }
+        };
+        HttpResponse::Ok().json(ids)
+    } else {
+        let elements = match total {
+            Some(0) => Page::empty(),
+            _ => {
+                let data = Set::find_operation(
+                    &mut tx, tid.as_str(), op_form.op, &sets, &query
+                ).await?;
+                Page::with_data(data, total, query.offset)
+            }
+        };
+        HttpResponse::Ok().json(elements)
+    };
+    app.commit_tx(tx).await?;
+    Ok(resp)
+}
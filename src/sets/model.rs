@@ -0,0 +1,703 @@
+use actix_contrib_rest::db::Tx;
+use actix_contrib_rest::query::QuerySearch;
+use actix_contrib_rest::result::{AppError, Result};
+use chrono::NaiveDateTime;
+use rand::random;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgQueryResult;
+use tracing::instrument;
+use validator::Validate;
+
+use crate::elements::model::{Element, ID_VALID};
+use crate::schemas::model::Schema;
+use crate::tenants::model::Tenant;
+
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct Set {
+    pub id: String,
+    #[serde(skip_serializing, default)]
+    pub tid: String,
+    pub name: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct SetPayload {
+    #[validate(length(min = 1, max = 256))]
+    #[validate(regex(
+        path = *ID_VALID,
+        code = "invalid_id",
+        message = "id can only contains letters, numbers or the symbols \\_~@-.:+, \
+        and must starts with a letter or number, or the symbols \\_~@"
+    ))]
+    pub id: Option<String>,
+    #[validate(length(min = 1, max = 80))]
+    pub name: String,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct SetPayloadEdition {
+    #[validate(length(min = 1, max = 80))]
+    pub name: String,
+}
+
+/// Payload to add elements to a set.
+#[derive(Deserialize, Serialize, Validate)]
+pub struct SetMembersPayload {
+    #[validate(length(min = 1, max = 1000))]
+    pub ids: Vec<String>,
+}
+
+/// Set algebra operations that can be computed
+/// between two or more sets.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SetOperation {
+    /// Elements that belong to any of the sets.
+    Union,
+    /// Elements that belong to all the sets.
+    Intersection,
+    /// Elements of the first set that don't belong to any of the other sets.
+    Difference,
+    /// Elements that belong to an odd number of the sets, that
+    /// with two sets are the elements that belong to only one of them.
+    SymmetricDifference,
+}
+
+impl SetOperation {
+    /// SQL sub-query that selects the `eid` of the elements resulting of the
+    /// operation, where `$1` is the tenant id and `$2` the array of set ids.
+    fn as_sql(&self) -> &'static str {
+        match self {
+            SetOperation::Union => r#"
+                SELECT DISTINCT eid FROM sets_elements
+                  WHERE tid = $1 AND sid = ANY($2::VARCHAR[])
+                "#,
+            SetOperation::Intersection => r#"
+                SELECT eid FROM sets_elements
+                  WHERE tid = $1 AND sid = ANY($2::VARCHAR[])
+                  GROUP BY eid HAVING COUNT(*) = CARDINALITY($2::VARCHAR[])
+                "#,
+            SetOperation::Difference => r#"
+                SELECT eid FROM sets_elements
+                  WHERE tid = $1 AND sid = ($2::VARCHAR[])[1]
+                EXCEPT
+                SELECT eid FROM sets_elements
+                  WHERE tid = $1 AND sid = ANY(($2::VARCHAR[])[2:])
+                "#,
+            SetOperation::SymmetricDifference => r#"
+                SELECT eid FROM sets_elements
+                  WHERE tid = $1 AND sid = ANY($2::VARCHAR[])
+                  GROUP BY eid HAVING COUNT(*) % 2 = 1
+                "#,
+        }
+    }
+
+    /// Same sub-query than [`SetOperation::as_sql()`], but without the elements in the trash.
+    fn live_sql(&self) -> String {
+        format!(
+            r#"
+            SELECT r.eid FROM ({}) AS r
+              JOIN elements e ON e.tid = $1 AND e.id = r.eid AND e.deleted_at IS NULL
+            "#,
+            self.as_sql()
+        )
+    }
+}
+
+/// Payload to compute an operation between sets.
+#[derive(Deserialize, Validate)]
+pub struct SetOperationPayload {
+    pub op: SetOperation,
+    /// Ids of the sets, the order only matters
+    /// for the [`SetOperation::Difference`] operation.
+    #[validate(length(min = 1, max = 100))]
+    pub sets: Vec<String>,
+    /// Return only the ids of the elements instead of the elements.
+    pub ids_only: Option<bool>,
+    /// If set, the result is stored as a new set instead
+    /// of returned in the response.
+    #[validate(nested)]
+    pub save_as: Option<SetPayload>,
+}
+
+impl SetOperationPayload {
+    /// The set ids without duplicates, keeping the order. The first set of a
+    /// [`SetOperation::Difference`] is kept apart, so if it's repeated
+    /// it's also subtracted.
+    pub fn unique_sets(&self) -> Vec<String> {
+        let mut sets: Vec<String> = Vec::with_capacity(self.sets.len());
+        let keep = match self.op {
+            SetOperation::Difference => 1,
+            _ => 0,
+        };
+        for sid in self.sets.iter() {
+            if sets.len() < keep || !sets[keep..].contains(sid) {
+                sets.push(sid.clone());
+            }
+        }
+        sets
+    }
+}
+
+/// Use to serialize the number of elements
+/// that were added to a set.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AddedCount {
+    pub added: u64,
+}
+
+/// Membership of an element to a set, as exported.
+#[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
+pub struct SetElementRecord {
+    pub set_id: String,
+    pub element_id: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl Set {
+    #[instrument(name = "Set::insert", skip_all, fields(tid = %tid))]
+    pub async fn insert(tx: &mut Tx<'_>, tid: &str, set_form: SetPayload) -> Result<Set> {
+        Tenant::exists_or_fail(tx, tid).await?;
+        let id = Self::new_id(tx, tid, set_form.id).await?;
+        let set = sqlx::query_as::<_, Set>(
+            "INSERT INTO sets (tid, id, name, created_at) \
+            VALUES ($1, $2, $3, NOW()) RETURNING *",
+            )
+            .bind(tid)
+            .bind(id.as_str())
+            .bind(set_form.name.as_str())
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(set)
+    }
+
+    /// The id for a new set, a random one if not passed,
+    /// failing if a set with the id passed already exists.
+    async fn new_id(tx: &mut Tx<'_>, tid: &str, id: Option<String>) -> Result<String> {
+        match id {
+            None => Ok(random::<u64>().to_string()),
+            Some(_id) => {
+                let exists = Self::exists(tx, tid, _id.as_str()).await?;
+                if exists {
+                    return Err(AppError::ResourceAlreadyExists {
+                        resource: "set",
+                        attribute: "id",
+                        value: _id,
+                    });
+                }
+                Ok(_id)
+            }
+        }
+    }
+
+    #[instrument(name = "Set::save", skip_all, fields(tid = %tid))]
+    pub async fn save(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        id: &str,
+        set_form: SetPayloadEdition
+    ) -> Result<Set> {
+        Tenant::exists_or_fail(tx, tid).await?;
+        let set = sqlx::query_as::<_, Set>(
+            "INSERT INTO sets (tid, id, name, created_at) \
+            VALUES ($1, $2, $3, NOW()) \
+            ON CONFLICT (tid,id) DO UPDATE SET name = EXCLUDED.name
+            RETURNING *",
+        )
+            .bind(tid)
+            .bind(id)
+            .bind(set_form.name.as_str())
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(set)
+    }
+
+    #[instrument(name = "Set::exists", skip_all, fields(tid = %tid))]
+    pub async fn exists(tx: &mut Tx<'_>, tid: &str, id: &str) -> Result<bool> {
+        let res: (bool,) = sqlx::query_as(
+            "SELECT EXISTS(SELECT id FROM sets WHERE tid = $1 AND id = $2)")
+            .bind(tid)
+            .bind(id)
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(res.0)
+    }
+
+    #[instrument(name = "Set::exists_or_fail", skip_all, fields(tid = %tid))]
+    pub async fn exists_or_fail(tx: &mut Tx<'_>, tid: &str, id: &str) -> Result<()> {
+        let set_exists = Set::exists(tx, tid, id).await?;
+        if !set_exists {
+            return Err(AppError::ResourceNotFound {
+                resource: "set",
+                attribute: "id",
+                value: id.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    #[instrument(name = "Set::get", skip_all, fields(tid = %tid))]
+    pub async fn get(tx: &mut Tx<'_>, tid: &str, id: &str) -> Result<Option<Set>> {
+        Tenant::exists_or_fail(tx, tid).await?;
+        let set: Option<Set> = sqlx::query_as(
+            "SELECT * FROM sets WHERE tid = $1 AND id = $2")
+            .bind(tid)
+            .bind(id)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(set)
+    }
+
+    #[instrument(name = "Set::delete", skip_all, fields(tid = %tid))]
+    pub async fn delete(tx: &mut Tx<'_>, tid: &str, id: &str) -> Result<u64> {
+        Tenant::exists_or_fail(tx, tid).await?;
+        // Memberships are removed by the "ON DELETE CASCADE" constraint
+        let res: PgQueryResult = sqlx::query(
+                "DELETE FROM sets WHERE tid = $1 AND id = $2")
+            .bind(tid)
+            .bind(id)
+            .execute(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+
+        Ok(res.rows_affected())
+    }
+
+    #[instrument(name = "Set::count", skip_all, fields(tid = %tid))]
+    pub async fn count(tx: &mut Tx<'_>, tid: &str, q: Option<&str>) -> Result<i64> {
+        let query = match q {
+            None => sqlx::query_as("SELECT COUNT(*) FROM sets WHERE tid = $1")
+                .bind(tid),
+            Some(q) => sqlx::query_as(
+                r#"
+                SELECT COUNT(*)
+                  FROM sets
+                  WHERE tid = $1 AND (id ILIKE $2 OR name ILIKE $2)
+                "#)
+                .bind(tid)
+                .bind(format!("%{q}%")),
+        };
+        let count: (i64,) = query.fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(count.0)
+    }
+
+    #[instrument(name = "Set::find", skip_all, fields(tid = %tid))]
+    pub async fn find(tx: &mut Tx<'_>, tid: &str, query: &QuerySearch) -> Result<Vec<Set>> {
+        let order = query.sort_as_order_by_args(&["id", "name", "created_at"], "id");
+        let sql;
+        let query = match query.q.as_deref() {
+            None => {
+                sql = format!(
+                    "SELECT * FROM sets WHERE tid = $1 ORDER BY {order} LIMIT $2 OFFSET $3"
+                );
+                sqlx::query_as(sql.as_str())
+                    .bind(tid)
+                    .bind(query.page_size)
+                    .bind(query.offset)
+            }
+            Some(q) => {
+                let name_like = format!("%{q}%");
+                sql = format!(
+                    r#"
+                SELECT *
+                  FROM sets
+                  WHERE tid = $1 AND (id ILIKE $2 OR name ILIKE $2)
+                  ORDER BY {order} LIMIT $3 OFFSET $4
+                    "#
+                );
+                sqlx::query_as(sql.as_str())
+                    .bind(tid)
+                    .bind(name_like)
+                    .bind(query.page_size)
+                    .bind(query.offset)
+            }
+        };
+        let sets = query.fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(sets)
+    }
+
+    /// Add the elements with the `ids` given to the set. Elements
+    /// already in the set are ignored, and if any of the elements
+    /// does not exist an error is returned and nothing is added.
+    #[instrument(name = "Set::add_elements", skip_all, fields(tid = %tid))]
+    pub async fn add_elements(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        id: &str,
+        ids: &[String],
+    ) -> Result<u64> {
+        Tenant::exists_or_fail(tx, tid).await?;
+        Self::exists_or_fail(tx, tid, id).await?;
+        let missing: Option<(String,)> = sqlx::query_as(
+                r#"
+            SELECT eid
+              FROM UNNEST($2::VARCHAR[]) AS eid
+              WHERE NOT EXISTS(SELECT id FROM elements WHERE tid = $1 AND id = eid AND deleted_at IS NULL)
+              LIMIT 1
+                "#
+            )
+            .bind(tid)
+            .bind(ids)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        if let Some((eid,)) = missing {
+            return Err(AppError::ResourceNotFound {
+                resource: "element",
+                attribute: "id",
+                value: eid,
+            });
+        }
+        Schema::validate_members(tx, tid, id, ids).await?;
+        let res: PgQueryResult = sqlx::query(
+                r#"
+            INSERT INTO sets_elements (tid, sid, eid, created_at)
+              SELECT $1, $2, eid, NOW() FROM UNNEST($3::VARCHAR[]) AS eid
+              ON CONFLICT DO NOTHING
+                "#
+            )
+            .bind(tid)
+            .bind(id)
+            .bind(ids)
+            .execute(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(res.rows_affected())
+    }
+
+    #[instrument(name = "Set::remove_element", skip_all, fields(tid = %tid))]
+    pub async fn remove_element(tx: &mut Tx<'_>, tid: &str, id: &str, eid: &str) -> Result<u64> {
+        Tenant::exists_or_fail(tx, tid).await?;
+        let res: PgQueryResult = sqlx::query(
+                "DELETE FROM sets_elements WHERE tid = $1 AND sid = $2 AND eid = $3")
+            .bind(tid)
+            .bind(id)
+            .bind(eid)
+            .execute(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(res.rows_affected())
+    }
+
+    #[instrument(name = "Set::count_elements", skip_all, fields(tid = %tid))]
+    pub async fn count_elements(tx: &mut Tx<'_>, tid: &str, id: &str) -> Result<i64> {
+        let count: (i64,) = sqlx::query_as(
+                r#"
+            SELECT COUNT(*)
+            FROM sets_elements
+            WHERE tid = $1 AND sid = $2 
+              AND EXISTS(SELECT id FROM elements WHERE tid = $1 AND id = eid AND deleted_at IS NULL)
+                "#
+        )
+            .bind(tid)
+            .bind(id)
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(count.0)
+    }
+
+    /// Find the ids of the elements that belong to the set,
+    /// the most recently added first.
+    #[instrument(name = "Set::find_elements", skip_all, fields(tid = %tid))]
+    pub async fn find_elements(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        id: &str,
+        query: &QuerySearch,
+    ) -> Result<Vec<String>> {
+        let ids: Vec<(String,)> = sqlx::query_as(
+                r#"
+            SELECT eid
+            FROM sets_elements
+            WHERE tid = $1 AND sid = $2 
+              AND EXISTS(SELECT id FROM elements WHERE tid = $1 AND id = eid AND deleted_at IS NULL)
+            ORDER BY created_at DESC, eid
+            LIMIT $3 OFFSET $4
+                "#
+            )
+            .bind(tid)
+            .bind(id)
+            .bind(query.page_size)
+            .bind(query.offset)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(ids.into_iter().map(|r| r.0).collect())
+    }
+
+    /// Find the sets sorted by id, only the ones after the id `after` if passed,
+    /// so all the sets of a tenant can be read in batches.
+    #[instrument(name = "Set::find_by_id", skip_all, fields(tid = %tid))]
+    pub async fn find_by_id(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        after: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<Set>> {
+        let sets: Vec<Set> = sqlx::query_as(
+                r#"
+            SELECT *
+            FROM sets
+            WHERE tid = $1 AND ($2::VARCHAR IS NULL OR id > $2)
+            ORDER BY id
+            LIMIT $3
+                "#
+            )
+            .bind(tid)
+            .bind(after)
+            .bind(limit)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(sets)
+    }
+
+    /// Find the memberships of all the sets sorted by set and element id, only
+    /// the ones after `after` if passed, except the ones of elements in the trash.
+    #[instrument(name = "Set::find_element_records", skip_all, fields(tid = %tid))]
+    pub async fn find_element_records(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        after: Option<(&str, &str)>,
+        limit: i64,
+    ) -> Result<Vec<SetElementRecord>> {
+        let (after_sid, after_eid) = after.unzip();
+        let records: Vec<SetElementRecord> = sqlx::query_as(
+                r#"
+            SELECT sid AS set_id, eid AS element_id, created_at
+            FROM sets_elements
+            WHERE tid = $1 AND ($2::VARCHAR IS NULL OR (sid, eid) > ($2, $3))
+              AND EXISTS(SELECT id FROM elements WHERE tid = $1 AND id = eid AND deleted_at IS NULL)
+            ORDER BY sid, eid
+            LIMIT $4
+                "#
+            )
+            .bind(tid)
+            .bind(after_sid)
+            .bind(after_eid)
+            .bind(limit)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(records)
+    }
+
+    /// Insert the sets as they are, keeping their creation time.
+    #[instrument(name = "Set::insert_records", skip_all, fields(tid = %tid))]
+    pub async fn insert_records(tx: &mut Tx<'_>, tid: &str, sets: Vec<Set>) -> Result<u64> {
+        let mut ids: Vec<String> = Vec::with_capacity(sets.len());
+        let mut names: Vec<String> = Vec::with_capacity(sets.len());
+        let mut created_at: Vec<NaiveDateTime> = Vec::with_capacity(sets.len());
+        for set in sets {
+            ids.push(set.id);
+            names.push(set.name);
+            created_at.push(set.created_at);
+        }
+        let res: PgQueryResult = sqlx::query(
+                r#"
+            INSERT INTO sets (tid, id, name, created_at)
+            SELECT $1, id, name, created_at
+            FROM UNNEST($2::VARCHAR[], $3::VARCHAR[], $4::TIMESTAMP[]) AS r(id, name, created_at)
+                "#
+            )
+            .bind(tid)
+            .bind(ids)
+            .bind(names)
+            .bind(created_at)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| match e.as_database_error() {
+                Some(db_error) if db_error.is_unique_violation() => AppError::StaticValidation(
+                    "sets with duplicated ids or already existing"
+                ),
+                _ => AppError::DB(e),
+            })?;
+        Ok(res.rows_affected())
+    }
+
+    /// Insert the memberships as they are, keeping the time the elements were
+    /// added, without validating the elements against the schemas of the sets.
+    #[instrument(name = "Set::insert_element_records", skip_all, fields(tid = %tid))]
+    pub async fn insert_element_records(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        records: Vec<SetElementRecord>,
+    ) -> Result<u64> {
+        let mut sids: Vec<String> = Vec::with_capacity(records.len());
+        let mut eids: Vec<String> = Vec::with_capacity(records.len());
+        let mut created_at: Vec<NaiveDateTime> = Vec::with_capacity(records.len());
+        for record in records {
+            sids.push(record.set_id);
+            eids.push(record.element_id);
+            created_at.push(record.created_at);
+        }
+        let res: PgQueryResult = sqlx::query(
+                r#"
+            INSERT INTO sets_elements (tid, sid, eid, created_at)
+            SELECT $1, sid, eid, created_at
+            FROM UNNEST($2::VARCHAR[], $3::VARCHAR[], $4::TIMESTAMP[]) AS r(sid, eid, created_at)
+            ON CONFLICT DO NOTHING
+                "#
+            )
+            .bind(tid)
+            .bind(sids)
+            .bind(eids)
+            .bind(created_at)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| match e.as_database_error() {
+                Some(db_error) if db_error.is_foreign_key_violation() => AppError::StaticValidation(
+                    "elements added to sets or sets not found"
+                ),
+                _ => AppError::DB(e),
+            })?;
+        Ok(res.rows_affected())
+    }
+
+    /// Check all the sets exist, failing with the
+    /// first set id that doesn't exist.
+    #[instrument(name = "Set::all_exist_or_fail", skip_all, fields(tid = %tid))]
+    pub async fn all_exist_or_fail(tx: &mut Tx<'_>, tid: &str, ids: &[String]) -> Result<()> {
+        let missing: Option<(String,)> = sqlx::query_as(
+                r#"
+            SELECT sid
+              FROM UNNEST($2::VARCHAR[]) AS sid
+              WHERE NOT EXISTS(SELECT id FROM sets WHERE tid = $1 AND id = sid)
+              LIMIT 1
+                "#
+            )
+            .bind(tid)
+            .bind(ids)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        if let Some((sid,)) = missing {
+            return Err(AppError::ResourceNotFound {
+                resource: "set",
+                attribute: "id",
+                value: sid,
+            });
+        }
+        Ok(())
+    }
+
+    /// Count the elements resulting of applying the operation `op` between the sets `ids`.
+    #[instrument(name = "Set::count_operation", skip_all, fields(tid = %tid))]
+    pub async fn count_operation(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        op: SetOperation,
+        ids: &[String],
+    ) -> Result<i64> {
+        let sql = format!("SELECT COUNT(*) FROM ({}) AS r", op.live_sql());
+        let count: (i64,) = sqlx::query_as(sql.as_str())
+            .bind(tid)
+            .bind(ids)
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(count.0)
+    }
+
+    /// Find the elements resulting of applying the operation `op`
+    /// between the sets `ids`, ordered by id.
+    #[instrument(name = "Set::find_operation", skip_all, fields(tid = %tid))]
+    pub async fn find_operation(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        op: SetOperation,
+        ids: &[String],
+        query: &QuerySearch,
+    ) -> Result<Vec<Element>> {
+        let sql = format!(
+            r#"
+            SELECT e.*
+            FROM elements e
+            JOIN ({}) AS r ON e.tid = $1 AND e.id = r.eid
+            WHERE e.deleted_at IS NULL
+            ORDER BY e.id
+            LIMIT $3 OFFSET $4
+            "#,
+            op.as_sql()
+        );
+        let elements: Vec<Element> = sqlx::query_as(sql.as_str())
+            .bind(tid)
+            .bind(ids)
+            .bind(query.page_size)
+            .bind(query.offset)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(elements)
+    }
+
+    /// Same as [`Set::find_operation()`] but only returning the ids of the elements.
+    #[instrument(name = "Set::find_operation_ids", skip_all, fields(tid = %tid))]
+    pub async fn find_operation_ids(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        op: SetOperation,
+        ids: &[String],
+        query: &QuerySearch,
+    ) -> Result<Vec<String>> {
+        let sql = format!(
+            "SELECT eid FROM ({}) AS r ORDER BY eid LIMIT $3 OFFSET $4",
+            op.live_sql()
+        );
+        let ids: Vec<(String,)> = sqlx::query_as(sql.as_str())
+            .bind(tid)
+            .bind(ids)
+            .bind(query.page_size)
+            .bind(query.offset)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(ids.into_iter().map(|r| r.0).collect())
+    }
+
+    /// Create a new set with the elements resulting of applying
+    /// the operation `op` between the sets `ids`.
+    #[instrument(name = "Set::insert_operation", skip_all, fields(tid = %tid))]
+    pub async fn insert_operation(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        op: SetOperation,
+        ids: &[String],
+        set_form: SetPayload,
+    ) -> Result<Set> {
+        Tenant::exists_or_fail(tx, tid).await?;
+        let id = Self::new_id(tx, tid, set_form.id).await?;
+        let sql = format!(
+            r#"
+            WITH s AS (
+              INSERT INTO sets (tid, id, name, created_at)
+              VALUES ($1, $3, $4, NOW()) RETURNING *
+            ), se AS (
+              INSERT INTO sets_elements (tid, sid, eid, created_at)
+              SELECT $1, s.id, r.eid, s.created_at FROM s, ({}) AS r
+            )
+            SELECT * FROM s
+            "#,
+            op.live_sql()
+        );
+        let set = sqlx::query_as::<_, Set>(sql.as_str())
+            .bind(tid)
+            .bind(ids)
+            .bind(id.as_str())
+            .bind(set_form.name.as_str())
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(set)
+    }
+}
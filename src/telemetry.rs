@@ -0,0 +1,207 @@
+//! Distributed tracing with OpenTelemetry, and request ids.
+//!
+//! Each request gets a span, child of the trace passed with the W3C
+//! `traceparent` header if any, and the calls to the models are traced
+//! with child spans. The exporter of the spans is set with the env
+//! variable `TRACE_EXPORTER`, by default `none`.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpMessage};
+use chrono::{DateTime, Utc};
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::trace::{Status, TracerProvider};
+use opentelemetry::{global, Context};
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{SdkTracerProvider, SpanData, SpanExporter};
+use opentelemetry_sdk::Resource;
+use serde_json::{json, Map, Value};
+use std::env;
+use std::str::FromStr;
+use tracing::subscriber::NoSubscriber;
+use tracing::{Instrument, Level};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::{Layer, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use uuid::Uuid;
+
+/// Header with the id of the request, taken from the request
+/// if valid or generated otherwise, and echoed in the response.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Where the spans are exported.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, strum_macros::Display, strum_macros::EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum TraceExporter {
+    /// Spans are not exported.
+    #[default]
+    None,
+    /// Spans are sent to an OpenTelemetry collector with the OTLP protocol
+    /// over HTTP, configured with the standard `OTEL_EXPORTER_OTLP_*` env
+    /// variables, e.g. `OTEL_EXPORTER_OTLP_ENDPOINT=http://localhost:4318`.
+    Otlp,
+    /// Spans are printed in the standard output as JSON lines, for local testing.
+    Stdout,
+}
+
+impl TraceExporter {
+    /// Read the exporter from the env variable `TRACE_EXPORTER`.
+    pub fn from_env() -> core::result::Result<Self, String> {
+        match env::var("TRACE_EXPORTER") {
+            Err(_) => Ok(TraceExporter::default()),
+            Ok(exporter) => TraceExporter::from_str(exporter.as_str())
+                .map_err(|_| format!("invalid TRACE_EXPORTER \"{exporter}\"")),
+        }
+    }
+}
+
+/// Id of the request, available to the handlers through the
+/// request extensions, e.g. `req.extensions().get::<RequestId>()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+/// Initialize the tracing of the app with the exporter passed, returning the
+/// provider of the spans, that has to be shut down when the app finishes.
+pub fn init(exporter: TraceExporter) -> core::result::Result<Option<SdkTracerProvider>, String> {
+    let builder = SdkTracerProvider::builder()
+        .with_resource(Resource::builder().with_service_name("backset").build());
+    let provider = match exporter {
+        TraceExporter::None => {
+            // Without a subscriber the spans are emitted as log records
+            tracing::subscriber::set_global_default(NoSubscriber::default()).ok();
+            return Ok(None);
+        }
+        TraceExporter::Otlp => {
+            let exporter = opentelemetry_otlp::SpanExporter::builder()
+                .with_http()
+                .build()
+                .map_err(|e| format!("error creating the OTLP exporter: {e}"))?;
+            builder.with_batch_exporter(exporter).build()
+        }
+        TraceExporter::Stdout => builder.with_simple_exporter(JsonSpanExporter).build(),
+    };
+    let tracer = provider.tracer("backset");
+    // Only the spans of the app, dependencies like the async runtimes have their own
+    let filter = Targets::new().with_target("backset", Level::TRACE);
+    tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(tracer).with_filter(filter))
+        .try_init()
+        .map_err(|e| format!("error initializing tracing: {e}"))?;
+    global::set_tracer_provider(provider.clone());
+    Ok(Some(provider))
+}
+
+/// Export the spans pending, if any, and stop the provider.
+pub fn shutdown(provider: Option<SdkTracerProvider>) {
+    if let Some(provider) = provider {
+        provider.shutdown().ok();
+    }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+/// Get the context of the trace propagated with the W3C `traceparent` header,
+/// or an empty context if the header is missing or not valid.
+pub fn parent_context(headers: &HeaderMap) -> Context {
+    TraceContextPropagator::new().extract(&HeaderExtractor(headers))
+}
+
+/// Get the request id passed in the header `X-Request-Id`, as long as
+/// it's not too long and has only visible ASCII characters, or
+/// generate a new one otherwise.
+///
+/// ```
+/// use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+/// use backset::telemetry::request_id;
+/// let mut headers = HeaderMap::new();
+/// assert_eq!(request_id(&headers).len(), 36);
+/// headers.insert(HeaderName::from_static("x-request-id"), HeaderValue::from_static("req-1"));
+/// assert_eq!(request_id(&headers), "req-1");
+/// ```
+pub fn request_id(headers: &HeaderMap) -> String {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= 128 && id.bytes().all(|b| b.is_ascii_graphic()))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// Middleware that traces the requests, and sets the `X-Request-Id` header
+/// in the responses, that can be logged with `%{x-request-id}o`.
+pub async fn request_tracing(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> core::result::Result<ServiceResponse<impl MessageBody>, Error> {
+    let request_id = request_id(req.headers());
+    let route = req.match_pattern().unwrap_or("unmatched".to_string());
+    let span = tracing::info_span!(
+        "HTTP request",
+        otel.name = format!("{} {}", req.method(), route),
+        otel.kind = "server",
+        http.request.method = %req.method(),
+        http.route = %route,
+        url.path = %req.path(),
+        request_id = %request_id,
+        http.response.status_code = tracing::field::Empty,
+    );
+    span.set_parent(parent_context(req.headers()));
+    req.extensions_mut().insert(RequestId(request_id.clone()));
+    let mut res = next.call(req).instrument(span.clone()).await?;
+    span.record("http.response.status_code", res.status().as_u16());
+    if let Ok(value) = HeaderValue::from_str(request_id.as_str()) {
+        res.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+    Ok(res)
+}
+
+/// Exporter that prints the spans as JSON lines in the standard output.
+#[derive(Debug)]
+struct JsonSpanExporter;
+
+impl JsonSpanExporter {
+    fn to_json(span: &SpanData) -> Value {
+        let attributes: Map<String, Value> = span.attributes.iter()
+            .map(|kv| (kv.key.to_string(), Value::String(kv.value.to_string())))
+            .collect();
+        let duration = span.end_time.duration_since(span.start_time).unwrap_or_default();
+        json!({
+            "trace_id": span.span_context.trace_id().to_string(),
+            "span_id": span.span_context.span_id().to_string(),
+            "parent_span_id": span.parent_span_id.to_string(),
+            "name": span.name,
+            "kind": format!("{:?}", span.span_kind),
+            "start_time": DateTime::<Utc>::from(span.start_time).to_rfc3339(),
+            "duration_ms": duration.as_secs_f64() * 1000.0,
+            "status": match &span.status {
+                Status::Unset => "unset".to_string(),
+                Status::Ok => "ok".to_string(),
+                Status::Error { description } => format!("error: {description}"),
+            },
+            "attributes": attributes,
+        })
+    }
+}
+
+impl SpanExporter for JsonSpanExporter {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        for span in batch.iter() {
+            println!("{}", Self::to_json(span));
+        }
+        Ok(())
+    }
+}
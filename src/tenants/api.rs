@@ -1,12 +1,20 @@
+use crate::tenants::export::{Exporter, Importer, MAX_LINE_SIZE};
 use crate::tenants::model::{Tenant, TenantPayload, TenantPayloadEdition};
 
+use crate::conditional::{etag, Preconditions};
+use crate::cursor::{CursorPage, CursorQuery};
+use crate::elements::bulk::NDJSON_CONTENT_TYPE;
+use crate::metrics::METRICS;
+use crate::trash::{IncludeDeletedQuery, SoftDeleteQuery};
+
 use actix_contrib_rest::app_state::AppState;
 use actix_contrib_rest::page::Page;
 use actix_contrib_rest::query::{Force, QuerySearch};
-use actix_contrib_rest::result::{DeletedCount, HttpResult};
-use actix_web::web::{Data, Path};
+use actix_contrib_rest::result::{AppError, DeletedCount, HttpResult};
+use actix_web::web::{BytesMut, Data, Path, Payload};
 use actix_web::{delete, get, post, put, HttpResponse};
 use actix_web_validator::{Json, Query};
+use futures_util::StreamExt;
 
 #[post("")]
 async fn create(app: Data<AppState>, tenant_form: Json<TenantPayload>) -> HttpResult {
@@ -15,38 +23,55 @@ async fn create(app: Data<AppState>, tenant_form: Json<TenantPayload>) -> HttpRe
     let tenant = Tenant::insert(&mut tx, tenant_form.0).await?;
 
     app.commit_tx(tx).await?;
-    Ok(HttpResponse::Created().json(tenant))
+    Ok(HttpResponse::Created().insert_header(etag(tenant.version)).json(tenant))
 }
 
 #[get("{id}")]
-async fn read(app: Data<AppState>, id: Path<String>) -> HttpResult {
+async fn read(
+    app: Data<AppState>,
+    id: Path<String>,
+    deleted_query: Query<IncludeDeletedQuery>,
+    preconditions: Preconditions,
+) -> HttpResult {
+    let include_deleted = deleted_query.include_deleted.unwrap_or(false);
     let mut tx = app.get_tx().await?;
 
-    let tenant = Tenant::get(&mut tx, id.into_inner().as_str()).await?;
+    let tenant = Tenant::get(&mut tx, id.into_inner().as_str(), include_deleted).await?;
 
     app.commit_tx(tx).await?;
     match tenant {
-        Some(t) => Ok(HttpResponse::Ok().json(t)),
+        Some(t) => match preconditions.evaluate(Some(t.version)) {
+            Some(res) => Ok(res),
+            None => Ok(HttpResponse::Ok().insert_header(etag(t.version)).json(t)),
+        },
         None => Ok(HttpResponse::NotFound().finish()),
     }
 }
 
 #[get("")]
-async fn list(app: Data<AppState>, query: Query<QuerySearch>) -> HttpResult {
+async fn list(
+    app: Data<AppState>,
+    query: Query<QuerySearch>,
+    cursor: Query<CursorQuery>,
+    deleted_query: Query<IncludeDeletedQuery>,
+) -> HttpResult {
     let query = query.into_inner();
+    let after: Option<String> = cursor.decode(&query)?;
+    let include_deleted = deleted_query.include_deleted.unwrap_or(false);
     let mut tx = app.get_tx().await?;
     let total = if query.include_total.unwrap_or(true) {
-        Some(Tenant::count(&mut tx, query.q.as_deref()).await?)
+        Some(Tenant::count(&mut tx, query.q.as_deref(), include_deleted).await?)
     } else {
         None
     };
     let tenants = match total {
         Some(0) => Page::empty(),
         _ => {
-            let data = Tenant::find(&mut tx, &query).await?;
+            let data = Tenant::find(&mut tx, &query, after.as_deref(), include_deleted).await?;
             Page::with_data(data, total, query.offset)
         }
     };
+    let tenants = CursorPage::new(tenants, &query, |t| t.id.clone());
     app.commit_tx(tx).await?;
     Ok(HttpResponse::Ok().json(tenants))
 }
@@ -56,8 +81,15 @@ async fn put(
     app: Data<AppState>,
     id: Path<String>,
     tenant_form: Json<TenantPayloadEdition>,
+    preconditions: Preconditions,
 ) -> HttpResult {
     let mut tx = app.get_tx().await?;
+    if !preconditions.is_empty() {
+        let version = Tenant::lock_version(&mut tx, id.as_str()).await?;
+        if let Some(res) = preconditions.evaluate(version) {
+            return Ok(res);
+        }
+    }
 
     let tenant = Tenant::save(
         &mut tx,
@@ -66,19 +98,39 @@ async fn put(
     ).await?;
 
     app.commit_tx(tx).await?;
-    Ok(HttpResponse::Ok().json(tenant))
+    Ok(HttpResponse::Ok().insert_header(etag(tenant.version)).json(tenant))
 }
 
 #[delete("{id}")]
-async fn delete(app: Data<AppState>, id: Path<String>, query: Query<Force>) -> HttpResult {
+async fn delete(
+    app: Data<AppState>,
+    id: Path<String>,
+    query: Query<Force>,
+    soft_query: Query<SoftDeleteQuery>,
+    preconditions: Preconditions,
+) -> HttpResult {
     let query = query.into_inner();
+    let soft = soft_query.soft.unwrap_or(false);
+    if soft && query.force.is_some() {
+        return Err(AppError::StaticValidation("argument \"force\" cannot be used with \"soft\""));
+    }
     let mut tx = app.get_tx().await?;
+    if !preconditions.is_empty() {
+        let version = Tenant::lock_version(&mut tx, id.as_str()).await?;
+        if let Some(res) = preconditions.evaluate(version) {
+            return Ok(res);
+        }
+    }
 
-    let rows_deleted = Tenant::delete(
-        &mut tx,
-        id.into_inner().as_str(),
-        query.force.unwrap_or(false),
-    ).await?;
+    let rows_deleted = if soft {
+        Tenant::soft_delete(&mut tx, id.as_str()).await?
+    } else {
+        Tenant::delete(
+            &mut tx,
+            id.into_inner().as_str(),
+            query.force.unwrap_or(false),
+        ).await?
+    };
 
     app.commit_tx(tx).await?;
     match rows_deleted {
@@ -90,3 +142,61 @@ async fn delete(app: Data<AppState>, id: Path<String>, query: Query<Force>) -> H
         })),
     }
 }
+
+#[post("{id}/restore")]
+async fn restore(app: Data<AppState>, id: Path<String>) -> HttpResult {
+    let mut tx = app.get_tx().await?;
+
+    let tenant = Tenant::restore(&mut tx, id.as_str()).await?;
+
+    app.commit_tx(tx).await?;
+    match tenant {
+        Some(t) => Ok(HttpResponse::Ok().insert_header(etag(t.version)).json(t)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[get("{id}/export")]
+async fn export(app: Data<AppState>, id: Path<String>) -> HttpResult {
+    // The TX is moved into the response stream, so it's taken from the pool
+    // directly to not borrow the state as `get_tx()` does
+    let tx = app.pool.as_ref()
+        .ok_or(AppError::StaticValidation("Pool not initialized"))?
+        .begin()
+        .await
+        .map_err(AppError::DB)?;
+
+    let exporter = Exporter::begin(tx, id.as_str()).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(NDJSON_CONTENT_TYPE)
+        .streaming(exporter.into_stream()))
+}
+
+#[post("{id}/import")]
+async fn import(app: Data<AppState>, id: Path<String>, mut payload: Payload) -> HttpResult {
+    let mut tx = app.get_tx().await?;
+    let mut importer = Importer::new(Some(id.as_str()));
+    let mut buf = BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        buf.extend_from_slice(&chunk.map_err(|e| AppError::Unexpected(e.into()))?);
+        while let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+            let line = buf.split_to(pos + 1);
+            importer.push_line(&mut tx, &line).await?;
+        }
+        if buf.len() > MAX_LINE_SIZE {
+            // The line is rejected as too long without waiting for its end
+            let line = buf.split();
+            importer.push_line(&mut tx, &line).await?;
+        }
+    }
+    importer.push_line(&mut tx, &buf).await?;
+
+    let result = importer.finish(&mut tx).await?;
+
+    app.commit_tx(tx).await?;
+    METRICS.elements_created
+        .with_label_values(&[result.tenant.id.as_str()])
+        .inc_by(result.elements);
+    Ok(HttpResponse::Created().json(result))
+}
@@ -0,0 +1,351 @@
+//! Export and import of a whole tenant as NDJSON (a JSON document by line),
+//! for backups and migrations between environments. Each line is a record
+//! with its `type`, the first one is the tenant, followed by its elements,
+//! sets, the elements of the sets, relationships and schemas, in that order:
+//!
+//! ```text
+//! {"type":"tenant","tenant":{"id":"t1","name":"T1","id_strategy":"random","created_at":"..."}}
+//! {"type":"element","element":{"id":"e1","name":"E1","created_at":"..."}}
+//! {"type":"set","set":{"id":"s1","name":"S1","created_at":"..."}}
+//! {"type":"set_element","set_element":{"set_id":"s1","element_id":"e1","created_at":"..."}}
+//! {"type":"relationship","relationship":{"from_id":"e1","rel_type":"owns","to_id":"e2","data":{},"created_at":"..."}}
+//! {"type":"schema","set_id":null,"schema":{"required":["name"]}}
+//! ```
+//!
+//! The elements in the trash are not exported, neither their relationships
+//! nor the sets they belong to.
+
+use actix_contrib_rest::db::Tx;
+use actix_contrib_rest::result::{AppError, Result};
+use actix_web::web::Bytes;
+use futures_util::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use validator::Validate;
+
+use crate::elements::model::{Element, ElementRecord};
+use crate::relationships::model::{Relationship, RelationshipPayload};
+use crate::schemas::model::Schema;
+use crate::sets::model::{Set, SetElementRecord, SetPayload};
+use crate::tenants::model::{IdStrategy, Tenant, TenantPayload};
+
+/// Number of records read or inserted at once.
+pub const BATCH_SIZE: usize = 500;
+
+/// Max size of a line to import, in bytes.
+pub const MAX_LINE_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record {
+    Tenant { tenant: Tenant },
+    Element { element: ElementRecord },
+    Set { set: Set },
+    SetElement { set_element: SetElementRecord },
+    Relationship { relationship: Relationship },
+    /// Schema of the tenant, or of the set `set_id`.
+    Schema { set_id: Option<String>, schema: Value },
+}
+
+/// The records serialized as lines.
+fn lines(records: impl Iterator<Item = Record>) -> Result<Bytes> {
+    let mut buf: Vec<u8> = Vec::new();
+    for record in records {
+        serde_json::to_writer(&mut buf, &record).map_err(|e| AppError::Unexpected(e.into()))?;
+        buf.push(b'\n');
+    }
+    Ok(Bytes::from(buf))
+}
+
+/// Part of the tenant to export next, with the key
+/// of the last record exported of that part, if any.
+enum Stage {
+    Tenant(Tenant),
+    Elements(Option<String>),
+    Sets(Option<String>),
+    SetElements(Option<(String, String)>),
+    Relationships(Option<(String, String, String)>),
+    Schemas,
+}
+
+/// Export of a tenant by chunks of lines, so it's not loaded in memory at once.
+pub struct Exporter<'a> {
+    tx: Option<Tx<'a>>,
+    tid: String,
+    stage: Option<Stage>,
+}
+
+impl<'a> Exporter<'a> {
+    /// Start the export of the tenant, failing if it doesn't exist. The transaction
+    /// passed is set as a read only snapshot, so the export is consistent even if
+    /// the tenant is modified meanwhile.
+    pub async fn begin(mut tx: Tx<'a>, tid: &str) -> Result<Self> {
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::DB)?;
+        let Some(tenant) = Tenant::get(&mut tx, tid, false).await? else {
+            return Err(AppError::ResourceNotFound {
+                resource: "tenant",
+                attribute: "id",
+                value: tid.to_string(),
+            });
+        };
+        Ok(Exporter { tx: Some(tx), tid: tid.to_string(), stage: Some(Stage::Tenant(tenant)) })
+    }
+
+    /// The next lines of the export, or `None` once everything is exported.
+    pub async fn next_chunk(&mut self) -> Result<Option<Bytes>> {
+        let Some(tx) = self.tx.as_mut() else {
+            return Ok(None);
+        };
+        let tid = self.tid.as_str();
+        let limit = BATCH_SIZE as i64;
+        while let Some(stage) = self.stage.take() {
+            let records: Vec<Record> = match stage {
+                Stage::Tenant(tenant) => {
+                    self.stage = Some(Stage::Elements(None));
+                    vec![Record::Tenant { tenant }]
+                }
+                Stage::Elements(after) => {
+                    let elements = Element::find_by_id(tx, tid, after.as_deref(), limit).await?;
+                    self.stage = Some(match elements.last() {
+                        Some(last) => Stage::Elements(Some(last.id.clone())),
+                        None => Stage::Sets(None),
+                    });
+                    elements.into_iter().map(|el| Record::Element { element: el.into() }).collect()
+                }
+                Stage::Sets(after) => {
+                    let sets = Set::find_by_id(tx, tid, after.as_deref(), limit).await?;
+                    self.stage = Some(match sets.last() {
+                        Some(last) => Stage::Sets(Some(last.id.clone())),
+                        None => Stage::SetElements(None),
+                    });
+                    sets.into_iter().map(|set| Record::Set { set }).collect()
+                }
+                Stage::SetElements(after) => {
+                    let after = after.as_ref().map(|(sid, eid)| (sid.as_str(), eid.as_str()));
+                    let set_elements = Set::find_element_records(tx, tid, after, limit).await?;
+                    self.stage = Some(match set_elements.last() {
+                        Some(last) => Stage::SetElements(
+                            Some((last.set_id.clone(), last.element_id.clone()))
+                        ),
+                        None => Stage::Relationships(None),
+                    });
+                    set_elements.into_iter().map(|set_element| Record::SetElement { set_element }).collect()
+                }
+                Stage::Relationships(after) => {
+                    let after = after.as_ref()
+                        .map(|(from_id, rel_type, to_id)| (from_id.as_str(), rel_type.as_str(), to_id.as_str()));
+                    let rels = Relationship::find_by_id(tx, tid, after, limit).await?;
+                    self.stage = Some(match rels.last() {
+                        Some(last) => Stage::Relationships(
+                            Some((last.from_id.clone(), last.rel_type.clone(), last.to_id.clone()))
+                        ),
+                        None => Stage::Schemas,
+                    });
+                    rels.into_iter().map(|relationship| Record::Relationship { relationship }).collect()
+                }
+                Stage::Schemas => {
+                    Schema::find_all(tx, tid).await?
+                        .into_iter()
+                        .map(|(set_id, schema)| Record::Schema { set_id, schema })
+                        .collect()
+                }
+            };
+            if !records.is_empty() {
+                return lines(records.into_iter()).map(Some);
+            }
+        }
+        if let Some(tx) = self.tx.take() {
+            tx.commit().await.map_err(AppError::DB)?;
+        }
+        Ok(None)
+    }
+}
+
+impl Exporter<'static> {
+    /// Stream with the lines of the export, that ends after the first error.
+    pub fn into_stream(self) -> impl Stream<Item = Result<Bytes>> {
+        stream::unfold(Some(self), |exporter| async move {
+            let mut exporter = exporter?;
+            match exporter.next_chunk().await {
+                Ok(Some(chunk)) => Some((Ok(chunk), Some(exporter))),
+                Ok(None) => None,
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+    }
+}
+
+/// Result of an import.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ImportResult {
+    pub tenant: Tenant,
+    /// Elements imported.
+    pub elements: u64,
+    /// Sets imported.
+    #[serde(default)]
+    pub sets: u64,
+    /// Relationships imported.
+    #[serde(default)]
+    pub relationships: u64,
+}
+
+/// Import of a tenant line by line, the elements, the elements of the sets and
+/// the relationships are inserted in batches, and once all is imported, the
+/// elements are validated with the schemas imported.
+pub struct Importer {
+    tid: Option<String>,
+    tenant: Option<Tenant>,
+    elements: Vec<ElementRecord>,
+    sets: Vec<Set>,
+    set_elements: Vec<SetElementRecord>,
+    relationships: Vec<Relationship>,
+    elements_imported: u64,
+    sets_imported: u64,
+    relationships_imported: u64,
+    line: usize,
+}
+
+impl Importer {
+    /// Create the importer, with the id the tenant is imported with,
+    /// or `None` to use the id of the tenant exported.
+    pub fn new(tid: Option<&str>) -> Self {
+        Importer {
+            tid: tid.map(str::to_string),
+            tenant: None,
+            elements: Vec::new(),
+            sets: Vec::new(),
+            set_elements: Vec::new(),
+            relationships: Vec::new(),
+            elements_imported: 0,
+            sets_imported: 0,
+            relationships_imported: 0,
+            line: 0,
+        }
+    }
+
+    fn invalid_line(&self, error: impl std::fmt::Display) -> AppError {
+        AppError::Validation(Some("invalid_record"), format!("invalid record in line {}: {error}", self.line))
+    }
+
+    /// Import the record of the line. The tenant is created with the first
+    /// line, failing if it already exists. The records that others depend on,
+    /// like the elements of the relationships, have to be imported first.
+    pub async fn push_line(&mut self, tx: &mut Tx<'_>, line: &[u8]) -> Result<()> {
+        self.line += 1;
+        if line.trim_ascii().is_empty() {
+            return Ok(());
+        }
+        if line.len() > MAX_LINE_SIZE {
+            return Err(self.invalid_line(format!("up to {MAX_LINE_SIZE} bytes are allowed")));
+        }
+        let record: Record = serde_json::from_slice(line).map_err(|e| self.invalid_line(e))?;
+        match (record, &self.tenant) {
+            (Record::Tenant { tenant }, None) => {
+                let tenant_form = TenantPayload {
+                    id: self.tid.take().unwrap_or(tenant.id),
+                    name: tenant.name,
+                    id_strategy: Some(tenant.id_strategy),
+                };
+                tenant_form.validate().map_err(|e| self.invalid_line(e))?;
+                self.tenant = Some(Tenant::insert(tx, tenant_form).await?);
+            }
+            (Record::Tenant { .. }, Some(_)) => {
+                return Err(self.invalid_line("only one tenant can be imported"));
+            }
+            (_, None) => {
+                return Err(self.invalid_line("the first record has to be the tenant"));
+            }
+            (Record::Element { element }, Some(_)) => {
+                element.validate().map_err(|e| self.invalid_line(e))?;
+                self.elements.push(element);
+            }
+            (Record::Set { set }, Some(_)) => {
+                let set_form = SetPayload { id: Some(set.id.clone()), name: set.name.clone() };
+                set_form.validate().map_err(|e| self.invalid_line(e))?;
+                self.flush_elements(tx).await?;
+                self.sets.push(set);
+            }
+            (Record::SetElement { set_element }, Some(_)) => {
+                self.flush_sets(tx).await?;
+                self.set_elements.push(set_element);
+            }
+            (Record::Relationship { relationship }, Some(_)) => {
+                let rel_form = RelationshipPayload {
+                    rel_type: relationship.rel_type.clone(),
+                    to_id: relationship.to_id.clone(),
+                    data: None,
+                };
+                rel_form.validate().map_err(|e| self.invalid_line(e))?;
+                self.flush_elements(tx).await?;
+                self.relationships.push(relationship);
+            }
+            (Record::Schema { set_id, schema }, Some(_)) => {
+                self.flush(tx).await?;
+                Schema::save(tx, self.tenant_id().as_str(), set_id.as_deref(), schema).await?;
+            }
+        }
+        let pending = self.elements.len() + self.sets.len() + self.set_elements.len() + self.relationships.len();
+        if pending >= BATCH_SIZE {
+            self.flush(tx).await?;
+        }
+        Ok(())
+    }
+
+    fn tenant_id(&self) -> String {
+        self.tenant.as_ref().map(|t| t.id.clone()).unwrap_or_default()
+    }
+
+    async fn flush_elements(&mut self, tx: &mut Tx<'_>) -> Result<()> {
+        if !self.elements.is_empty() {
+            let batch = std::mem::take(&mut self.elements);
+            self.elements_imported += Element::insert_records(tx, self.tenant_id().as_str(), batch).await?;
+        }
+        Ok(())
+    }
+
+    async fn flush_sets(&mut self, tx: &mut Tx<'_>) -> Result<()> {
+        self.flush_elements(tx).await?;
+        if !self.sets.is_empty() {
+            let batch = std::mem::take(&mut self.sets);
+            self.sets_imported += Set::insert_records(tx, self.tenant_id().as_str(), batch).await?;
+        }
+        Ok(())
+    }
+
+    /// Insert all the records pending, the ones others depend on first.
+    async fn flush(&mut self, tx: &mut Tx<'_>) -> Result<()> {
+        self.flush_sets(tx).await?;
+        let tid = self.tenant_id();
+        if !self.set_elements.is_empty() {
+            let batch = std::mem::take(&mut self.set_elements);
+            Set::insert_element_records(tx, tid.as_str(), batch).await?;
+        }
+        if !self.relationships.is_empty() {
+            let batch = std::mem::take(&mut self.relationships);
+            self.relationships_imported += Relationship::insert_records(tx, tid.as_str(), batch).await?;
+        }
+        Ok(())
+    }
+
+    /// Insert the records pending and validate the elements with
+    /// the schemas imported, failing if no tenant was imported.
+    pub async fn finish(mut self, tx: &mut Tx<'_>) -> Result<ImportResult> {
+        self.flush(tx).await?;
+        let Some(tenant) = self.tenant else {
+            return Err(AppError::StaticValidation("no tenant found to import"));
+        };
+        Schema::validate_all(tx, tenant.id.as_str()).await?;
+        if tenant.id_strategy == IdStrategy::Sequence {
+            Tenant::reset_id_seq(tx, tenant.id.as_str()).await?;
+        }
+        Ok(ImportResult {
+            tenant,
+            elements: self.elements_imported,
+            sets: self.sets_imported,
+            relationships: self.relationships_imported,
+        })
+    }
+}
@@ -1,26 +1,68 @@
 use actix_contrib_rest::db::Tx;
 use actix_contrib_rest::query::QuerySearch;
 use actix_contrib_rest::result::{AppError, Result};
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, TimeDelta};
+use rand::random;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
 use sqlx::postgres::PgQueryResult;
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::sync::LazyLock;
+use tracing::instrument;
+use ulid::Ulid;
+use uuid::Uuid;
 use validator::{Validate, ValidationError};
 
+use crate::elements::model::Element;
+
 static ID_VALID: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[a-z][a-z0-9\-]+$").unwrap());
 
+/// Strategy used to generate the id of the elements
+/// of a tenant when it is not provided.
+#[derive(
+    Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, sqlx::Type,
+    strum_macros::Display, strum_macros::EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "VARCHAR", rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum IdStrategy {
+    /// A big random number.
+    #[default]
+    Random,
+    /// A random UUID (version 4).
+    Uuidv4,
+    /// A time-ordered UUID (version 7).
+    Uuidv7,
+    /// A time-ordered ULID.
+    Ulid,
+    /// A number incremented by one on each new element of the tenant.
+    Sequence,
+    /// The SHA-256 hash of the attributes of the element, so elements
+    /// with the same content cannot be inserted twice.
+    Hash,
+}
+
 #[derive(Debug, Deserialize, sqlx::FromRow, Serialize, Clone)]
 pub struct Tenant {
     pub id: String,
     pub name: String,
+    #[serde(default)]
+    pub id_strategy: IdStrategy,
     pub created_at: NaiveDateTime,
+    /// Incremented on each update, it's responded as `ETag`.
+    #[serde(skip_serializing, default)]
+    pub version: i64,
+    /// Time when the tenant was moved to the trash, if it was.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 fn validate_forbidden_list(tenant_id: &str) -> core::result::Result<(), ValidationError> {
-    if tenant_id == "tenants" || tenant_id == "health" {
+    if tenant_id == "tenants" || tenant_id == "health" || tenant_id == "metrics" {
         // id cannot collide with endpoint paths
         return Err(ValidationError {
             code: Cow::from("forbidden_id"),
@@ -43,6 +85,7 @@ pub struct TenantPayload {
     pub id: String,
     #[validate(length(min = 3, max = 80))]
     pub name: String,
+    pub id_strategy: Option<IdStrategy>,
 }
 
 #[derive(Deserialize, Validate)]
@@ -52,8 +95,10 @@ pub struct TenantPayloadEdition {
 }
 
 impl Tenant {
+    #[instrument(name = "Tenant::insert", skip_all)]
     pub async fn insert(tx: &mut Tx<'_>, tenant_form: TenantPayload) -> Result<Tenant> {
-        let exists = Self::exists(&mut *tx, tenant_form.id.as_str()).await?;
+        // Tenants in the trash keep their id until they are purged
+        let exists = Self::exists(&mut *tx, tenant_form.id.as_str(), true).await?;
         if exists {
             return Err(AppError::ResourceAlreadyExists {
                 resource: "tenant",
@@ -70,16 +115,19 @@ impl Tenant {
             });
         }
         let tenant = sqlx::query_as::<_, Tenant>(
-                "INSERT INTO tenants (id, name, created_at) VALUES ($1, $2, NOW()) RETURNING *",
+                "INSERT INTO tenants (id, name, id_strategy, created_at) \
+                VALUES ($1, $2, $3, NOW()) RETURNING *",
             )
             .bind(tenant_form.id.as_str())
             .bind(tenant_form.name.as_str())
+            .bind(tenant_form.id_strategy.unwrap_or_default())
             .fetch_one(&mut **tx)
             .await
             .map_err(AppError::DB)?;
         Ok(tenant)
     }
 
+    #[instrument(name = "Tenant::save", skip_all, fields(tid = %tid))]
     pub async fn save(
         tx: &mut Tx<'_>,
         tid: &str,
@@ -99,32 +147,41 @@ impl Tenant {
                 format!("name \"{name}\" already taken by tenant with id \"{duplicated_id}\"")
             ));
         }
-        let element = sqlx::query_as::<_, Tenant>(
+        let tenant = sqlx::query_as::<_, Tenant>(
             "INSERT INTO tenants (id, name, created_at) \
             VALUES ($1, $2, NOW()) \
-            ON CONFLICT (id) DO UPDATE SET name = EXCLUDED.name
+            ON CONFLICT (id) DO UPDATE SET name = EXCLUDED.name, version = tenants.version + 1
+            WHERE tenants.deleted_at IS NULL
             RETURNING *",
         )
             .bind(tid)
             .bind(name)
-            .fetch_one(&mut **tx)
+            .fetch_optional(&mut **tx)
             .await
             .map_err(AppError::DB)?;
-        Ok(element)
+        // Nothing is returned if the tenant is in the trash
+        tenant.ok_or_else(|| AppError::ResourceNotFound {
+            resource: "tenant",
+            attribute: "id",
+            value: tid.to_string(),
+        })
     }
 
-    pub async fn exists(tx: &mut Tx<'_>, tid: &str) -> Result<bool> {
+    #[instrument(name = "Tenant::exists", skip_all, fields(tid = %tid))]
+    pub async fn exists(tx: &mut Tx<'_>, tid: &str, include_deleted: bool) -> Result<bool> {
         let res: (bool,) = sqlx::query_as(
-                "SELECT EXISTS(SELECT id FROM tenants WHERE id = $1)")
+                "SELECT EXISTS(SELECT id FROM tenants WHERE id = $1 AND ($2 OR deleted_at IS NULL))")
             .bind(tid)
+            .bind(include_deleted)
             .fetch_one(&mut **tx)
             .await
             .map_err(AppError::DB)?;
         Ok(res.0)
     }
 
+    #[instrument(name = "Tenant::exists_or_fail", skip_all, fields(tid = %tid))]
     pub async fn exists_or_fail(tx: &mut Tx<'_>, tid: &str) -> Result<()> {
-        let tenant_exists = Tenant::exists(tx, tid).await?;
+        let tenant_exists = Tenant::exists(tx, tid, false).await?;
         if !tenant_exists {
             return Err(AppError::ResourceNotFound {
                 resource: "tenant",
@@ -135,6 +192,89 @@ impl Tenant {
         Ok(())
     }
 
+    /// Get the version of the tenant locking it until the transaction ends,
+    /// so it can be checked before updating it, or `None` if not found
+    /// or in the trash.
+    #[instrument(name = "Tenant::lock_version", skip_all, fields(tid = %tid))]
+    pub async fn lock_version(tx: &mut Tx<'_>, tid: &str) -> Result<Option<i64>> {
+        let res: Option<(i64,)> = sqlx::query_as(
+                "SELECT version FROM tenants WHERE id = $1 AND deleted_at IS NULL FOR UPDATE")
+            .bind(tid)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(res.map(|r| r.0))
+    }
+
+    /// Generate the id of a new element of the tenant, with the id strategy
+    /// of the tenant, failing if the tenant doesn't exist.
+    #[instrument(name = "Tenant::next_element_id", skip_all, fields(tid = %tid))]
+    pub async fn next_element_id(
+        tx: &mut Tx<'_>,
+        tid: &str,
+        data: &Map<String, Value>,
+    ) -> Result<String> {
+        let res: Option<(IdStrategy,)> = sqlx::query_as(
+                "SELECT id_strategy FROM tenants WHERE id = $1")
+            .bind(tid)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        let Some((strategy,)) = res else {
+            return Err(AppError::ResourceNotFound {
+                resource: "tenant",
+                attribute: "id",
+                value: tid.to_string(),
+            });
+        };
+        let id = match strategy {
+            IdStrategy::Random => random::<u64>().to_string(),
+            IdStrategy::Uuidv4 => Uuid::new_v4().to_string(),
+            IdStrategy::Uuidv7 => Uuid::now_v7().to_string(),
+            IdStrategy::Ulid => Ulid::new().to_string(),
+            IdStrategy::Sequence => loop {
+                // The row lock makes the sequence gapless and safe with concurrent inserts
+                let seq: (i64,) = sqlx::query_as(
+                        "UPDATE tenants SET id_seq = id_seq + 1 WHERE id = $1 RETURNING id_seq")
+                    .bind(tid)
+                    .fetch_one(&mut **tx)
+                    .await
+                    .map_err(AppError::DB)?;
+                let id = seq.0.to_string();
+                // Numbers already taken by ids set by the clients are skipped
+                if !Element::exists(tx, tid, id.as_str(), true).await? {
+                    break id;
+                }
+            },
+            IdStrategy::Hash => {
+                // Keys are serialized sorted, so the same content has always the same hash
+                let json = serde_json::to_vec(data).map_err(|e| AppError::Unexpected(e.into()))?;
+                format!("{:x}", Sha256::digest(json))
+            }
+        };
+        Ok(id)
+    }
+
+    /// Set the last value of the "sequence" id strategy to the
+    /// greatest numeric id of the elements, e.g. after importing them.
+    #[instrument(name = "Tenant::reset_id_seq", skip_all, fields(tid = %tid))]
+    pub async fn reset_id_seq(tx: &mut Tx<'_>, tid: &str) -> Result<()> {
+        sqlx::query(
+                r#"
+            UPDATE tenants SET id_seq = (
+              SELECT COALESCE(MAX(id::BIGINT), 0) FROM elements WHERE tid = $1 AND id ~ '^[0-9]{1,18}$'
+            )
+            WHERE id = $1
+                "#
+            )
+            .bind(tid)
+            .execute(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(())
+    }
+
+    #[instrument(name = "Tenant::get_id_by_name", skip_all)]
     pub async fn get_id_by_name(tx: &mut Tx<'_>, name: &str) -> Result<Option<String>> {
         let res: Option<(String,)> = sqlx::query_as("SELECT id FROM tenants WHERE name = $1")
             .bind(name)
@@ -144,26 +284,44 @@ impl Tenant {
         Ok(res.map(|r| r.0))
     }
 
-    pub async fn get(tx: &mut Tx<'_>, tid: &str) -> Result<Option<Tenant>> {
+    /// Get the tenant, if `include_deleted` even if it's in the trash.
+    #[instrument(name = "Tenant::get", skip_all, fields(tid = %tid))]
+    pub async fn get(tx: &mut Tx<'_>, tid: &str, include_deleted: bool) -> Result<Option<Tenant>> {
         let tenant: Option<Tenant> = sqlx::query_as(
-                "SELECT id, name, created_at FROM tenants WHERE id = $1")
+                r#"
+            SELECT id, name, id_strategy, created_at, version, deleted_at
+            FROM tenants
+            WHERE id = $1 AND ($2 OR deleted_at IS NULL)
+                "#
+            )
             .bind(tid)
+            .bind(include_deleted)
             .fetch_optional(&mut **tx)
             .await
             .map_err(AppError::DB)?;
         Ok(tenant)
     }
 
-    pub async fn count(tx: &mut Tx<'_>, q: Option<&str>) -> Result<i64> {
+    #[instrument(name = "Tenant::count", skip_all)]
+    pub async fn count(tx: &mut Tx<'_>, q: Option<&str>, include_deleted: bool) -> Result<i64> {
+        let deleted = if include_deleted { "TRUE" } else { "deleted_at IS NULL" };
+        let sql;
         let query = match q {
-            None => sqlx::query_as("SELECT COUNT(*) FROM tenants"),
-            Some(q) => sqlx::query_as(
-                r#"
+            None => {
+                sql = format!("SELECT COUNT(*) FROM tenants WHERE {deleted}");
+                sqlx::query_as(sql.as_str())
+            }
+            Some(q) => {
+                sql = format!(
+                    r#"
                 SELECT COUNT(*)
                   FROM tenants
-                  WHERE id ILIKE $1 OR name ILIKE $1
-                "#)
-                .bind(format!("%{q}%")),
+                  WHERE (id ILIKE $1 OR name ILIKE $1) AND {deleted}
+                    "#
+                );
+                sqlx::query_as(sql.as_str())
+                    .bind(format!("%{q}%"))
+            }
         };
         let count: (i64,) = query.fetch_one(&mut **tx)
             .await
@@ -171,23 +329,37 @@ impl Tenant {
         Ok(count.0)
     }
 
-    pub async fn find(tx: &mut Tx<'_>, query: &QuerySearch) -> Result<Vec<Tenant>> {
+    /// Find the tenants, if `after` is passed only the tenants
+    /// with an id greater than it are returned (cursor pagination),
+    /// and if `include_deleted` the ones in the trash too.
+    #[instrument(name = "Tenant::find", skip_all)]
+    pub async fn find(
+        tx: &mut Tx<'_>,
+        query: &QuerySearch,
+        after: Option<&str>,
+        include_deleted: bool,
+    ) -> Result<Vec<Tenant>> {
         let order = query.sort_as_order_by_args(&["id", "name", "created_at"], "id");
+        let deleted = if include_deleted { "TRUE" } else { "deleted_at IS NULL" };
         let sql;
         let query = match query.q.as_deref() {
             None => {
-                sql = format!("SELECT * FROM tenants ORDER BY {order} LIMIT $1 OFFSET $2");
+                let keyset = if after.is_some() { "AND id > $3" } else { "" };
+                sql = format!(
+                    "SELECT * FROM tenants WHERE {deleted} {keyset} ORDER BY {order} LIMIT $1 OFFSET $2"
+                );
                 sqlx::query_as(sql.as_str())
                     .bind(query.page_size)
                     .bind(query.offset)
             }
             Some(q) => {
                 let name_like = format!("%{q}%");
+                let keyset = if after.is_some() { "AND id > $4" } else { "" };
                 sql = format!(
                     r#"
                 SELECT *
                   FROM tenants
-                  WHERE id ILIKE $1 OR name ILIKE $1
+                  WHERE (id ILIKE $1 OR name ILIKE $1) AND {deleted} {keyset}
                   ORDER BY {order} LIMIT $2 OFFSET $3
                     "#
                 );
@@ -197,12 +369,17 @@ impl Tenant {
                     .bind(query.offset)
             }
         };
+        let query = match after {
+            Some(after) => query.bind(after),
+            None => query,
+        };
         let tenants = query.fetch_all(&mut **tx)
             .await
             .map_err(AppError::DB)?;
         Ok(tenants)
     }
 
+    #[instrument(name = "Tenant::has_elements", skip_all, fields(tid = %tid))]
     pub async fn has_elements(tx: &mut Tx<'_>, tid: &str) -> Result<bool> {
         let res: (bool,) = sqlx::query_as(
                 "SELECT EXISTS(SELECT id FROM elements WHERE tid = $1)"
@@ -214,6 +391,19 @@ impl Tenant {
         Ok(res.0)
     }
 
+    #[instrument(name = "Tenant::has_sets", skip_all, fields(tid = %tid))]
+    pub async fn has_sets(tx: &mut Tx<'_>, tid: &str) -> Result<bool> {
+        let res: (bool,) = sqlx::query_as(
+                "SELECT EXISTS(SELECT id FROM sets WHERE tid = $1)"
+            )
+            .bind(tid)
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(res.0)
+    }
+
+    #[instrument(name = "Tenant::delete", skip_all, fields(tid = %tid))]
     pub async fn delete(tx: &mut Tx<'_>, tid: &str, force: bool) -> Result<u64> {
         let has_to_delete_elements = if force {
             true
@@ -224,10 +414,29 @@ impl Tenant {
                     "cannot delete tenant with elements",
                 ));
             }
+            let has_sets = Self::has_sets(&mut *tx, tid).await?;
+            if has_sets {
+                return Err(AppError::StaticValidation(
+                    "cannot delete tenant with sets",
+                ));
+            }
             false
         };
         let mut rows_affected: u64 = 0;
         if has_to_delete_elements {
+            let res: PgQueryResult = sqlx::query("DELETE FROM relationships WHERE tid = $1")
+                .bind(tid)
+                .execute(&mut **tx)
+                .await
+                .map_err(AppError::DB)?;
+            rows_affected += res.rows_affected();
+            // Sets memberships are removed by the "ON DELETE CASCADE" constraint
+            let res: PgQueryResult = sqlx::query("DELETE FROM sets WHERE tid = $1")
+                .bind(tid)
+                .execute(&mut **tx)
+                .await
+                .map_err(AppError::DB)?;
+            rows_affected += res.rows_affected();
             let res: PgQueryResult = sqlx::query("DELETE FROM elements WHERE tid = $1")
                 .bind(tid)
                 .execute(&mut **tx)
@@ -235,6 +444,13 @@ impl Tenant {
                 .map_err(AppError::DB)?;
             rows_affected += res.rows_affected();
         }
+        // Revisions are kept after the elements are deleted, so they may exist
+        // anyway, they are not counted as they are not resources on their own
+        sqlx::query("DELETE FROM element_revisions WHERE tid = $1")
+            .bind(tid)
+            .execute(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
         let res: PgQueryResult = sqlx::query("DELETE FROM tenants WHERE id = $1")
             .bind(tid)
             .execute(&mut **tx)
@@ -244,4 +460,50 @@ impl Tenant {
 
         Ok(rows_affected)
     }
+
+    /// Move the tenant to the trash, so it and its elements are not accessible
+    /// anymore until it's restored, returning 0 if not found or already there.
+    #[instrument(name = "Tenant::soft_delete", skip_all, fields(tid = %tid))]
+    pub async fn soft_delete(tx: &mut Tx<'_>, tid: &str) -> Result<u64> {
+        let res: PgQueryResult = sqlx::query(
+                r#"
+            UPDATE tenants SET deleted_at = NOW(), version = version + 1
+            WHERE id = $1 AND deleted_at IS NULL
+                "#
+            )
+            .bind(tid)
+            .execute(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(res.rows_affected())
+    }
+
+    /// Restore the tenant from the trash, or `None` if it's not there.
+    #[instrument(name = "Tenant::restore", skip_all, fields(tid = %tid))]
+    pub async fn restore(tx: &mut Tx<'_>, tid: &str) -> Result<Option<Tenant>> {
+        let tenant: Option<Tenant> = sqlx::query_as(
+                r#"
+            UPDATE tenants SET deleted_at = NULL, version = version + 1
+            WHERE id = $1 AND deleted_at IS NOT NULL
+            RETURNING id, name, id_strategy, created_at, version, deleted_at
+                "#
+            )
+            .bind(tid)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(tenant)
+    }
+
+    /// Find the ids of the tenants moved to the trash before `older_than` ago.
+    #[instrument(name = "Tenant::find_deleted", skip_all)]
+    pub async fn find_deleted(tx: &mut Tx<'_>, older_than: TimeDelta) -> Result<Vec<String>> {
+        let ids: Vec<(String,)> = sqlx::query_as(
+                "SELECT id FROM tenants WHERE deleted_at < NOW() - $1 ORDER BY id")
+            .bind(older_than)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(AppError::DB)?;
+        Ok(ids.into_iter().map(|r| r.0).collect())
+    }
 }
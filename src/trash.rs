@@ -0,0 +1,81 @@
+//! Trash of tenants and elements: when they are soft deleted with `?soft=true`
+//! they are only marked as deleted, excluded from the responses unless
+//! `?include_deleted=true` is passed, so they can be restored later, until
+//! they are purged with the `purge` command.
+
+use actix_contrib_rest::db::Tx;
+use actix_contrib_rest::result::Result;
+use chrono::TimeDelta;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::elements::model::Element;
+use crate::tenants::model::Tenant;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct SoftDeleteQuery {
+    /// Move the resource to the trash instead of deleting it.
+    pub soft: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct IncludeDeletedQuery {
+    /// Include the resources in the trash.
+    pub include_deleted: Option<bool>,
+}
+
+/// Parse an age like "30d", with the units "s" (seconds), "m" (minutes),
+/// "h" (hours), "d" (days) or "w" (weeks).
+///
+/// ```
+/// use backset::trash::parse_age;
+/// use chrono::TimeDelta;
+/// assert_eq!(parse_age("30d"), Ok(TimeDelta::days(30)));
+/// assert_eq!(parse_age("12h"), Ok(TimeDelta::hours(12)));
+/// assert!(parse_age("30").is_err());
+/// assert!(parse_age("-1d").is_err());
+/// ```
+pub fn parse_age(age: &str) -> core::result::Result<TimeDelta, String> {
+    let error = || format!("invalid age \"{age}\", expected a number and a unit: s, m, h, d or w");
+    let Some(unit) = age.chars().last() else {
+        return Err(error());
+    };
+    let amount: i64 = age[..age.len() - unit.len_utf8()].parse().map_err(|_| error())?;
+    if amount < 0 {
+        return Err(error());
+    }
+    let delta = match unit {
+        's' => TimeDelta::try_seconds(amount),
+        'm' => TimeDelta::try_minutes(amount),
+        'h' => TimeDelta::try_hours(amount),
+        'd' => TimeDelta::try_days(amount),
+        'w' => TimeDelta::try_weeks(amount),
+        _ => None,
+    };
+    delta.ok_or_else(error)
+}
+
+/// Result of a purge.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PurgeResult {
+    /// Tenants deleted, with all their elements.
+    pub tenants: u64,
+    /// Elements deleted from tenants not in the trash.
+    pub elements: u64,
+}
+
+/// Delete for good the tenants and elements moved to the trash before `older_than`
+/// ago, with their relationships. The elements of tenants in the trash are kept
+/// until the tenant is purged or restored.
+pub async fn purge(tx: &mut Tx<'_>, older_than: TimeDelta) -> Result<PurgeResult> {
+    let mut result = PurgeResult { tenants: 0, elements: 0 };
+    for tid in Tenant::find_deleted(tx, older_than).await? {
+        Tenant::delete(tx, tid.as_str(), true).await?;
+        result.tenants += 1;
+    }
+    for (tid, id) in Element::find_deleted(tx, older_than).await? {
+        Element::delete(tx, tid.as_str(), id.as_str(), true).await?;
+        result.elements += 1;
+    }
+    Ok(result)
+}
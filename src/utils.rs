@@ -1,11 +1,30 @@
 use actix_contrib_rest::result::{AppError, Result};
+use serde::ser::SerializeMap;
 use serde_json::{Map, Value};
 use sqlx::types::Json;
 
-pub fn reject_created_at(data: &Json<Map<String, Value>>) -> Result<()> {
-    if data.contains_key("created_at") {
-        return Err(AppError::StaticValidation(
-            "cannot provide reserved attribute \"created_at\""));
+/// Attributes set by the server that cannot be provided in the payloads.
+pub const RESERVED_ATTRIBUTES: [&str; 4] = ["created_at", "updated_at", "updated_by", "deleted_at"];
+
+pub fn reject_reserved_attributes(data: &Json<Map<String, Value>>) -> Result<()> {
+    match RESERVED_ATTRIBUTES.iter().find(|attr| data.contains_key(**attr)) {
+        Some(attr) => Err(AppError::Validation(
+            None, format!("cannot provide reserved attribute \"{attr}\""))),
+        None => Ok(()),
+    }
+}
+
+/// Serialize the attributes of an element in the map, except the ones named as
+/// the `fields` set by the server serialized with them, so keys are not repeated,
+/// e.g. attributes of elements created before the server set a field with the
+/// same name. The attributes are kept, only the field of the server is responded.
+pub fn serialize_attributes<M: SerializeMap>(
+    map: &mut M,
+    data: &Map<String, Value>,
+    fields: &[&str],
+) -> core::result::Result<(), M::Error> {
+    for (key, value) in data.iter().filter(|(key, _)| !fields.contains(&key.as_str())) {
+        map.serialize_entry(key, value)?;
     }
     Ok(())
 }
@@ -5,14 +5,19 @@ mod tests {
     use actix_contrib_rest::result::ValidationErrorPayload;
     use actix_contrib_rest::test::assert_status;
     use actix_web::http::StatusCode;
-    use actix_web::test::{call_service, init_service, try_read_body_json, TestRequest};
+    use actix_web::test::{call_service, init_service, read_body, try_read_body_json, TestRequest};
     use actix_web::App;
     use backset::app_server::AppServer;
-    use backset::elements::model::ElementPayload;
+    use backset::cursor::CursorPage;
+    use backset::elements::bulk::BulkResult;
+    use backset::elements::model::{Element, ElementPayload, ElementsFound};
+    use backset::tenants::model::Tenant;
     use backset::PAGE_SIZE;
     use pretty_assertions::assert_eq;
     use rand::random;
+    use regex::Regex;
     use serde_json::json;
+    use sqlx::Connection;
     use std::error::Error;
 
     #[actix_web::test]
@@ -90,6 +95,79 @@ mod tests {
         Ok(())
     }
 
+    #[actix_web::test]
+    async fn test_elements_post_with_id_strategies() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        let rand_for_test = random::<u32>();
+        for (strategy, id_regex) in [
+            ("random", r"^[0-9]+$"),
+            ("uuidv4", r"^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-[0-9a-f]{4}-[0-9a-f]{12}$"),
+            ("uuidv7", r"^[0-9a-f]{8}-[0-9a-f]{4}-7[0-9a-f]{3}-[0-9a-f]{4}-[0-9a-f]{12}$"),
+            ("ulid", r"^[0-9A-Z]{26}$"),
+            ("sequence", r"^[12]$"),
+            ("hash", r"^[0-9a-f]{64}$"),
+        ] {
+            let tid = format!("ids-{strategy}-{rand_for_test}");
+            let req = post("/tenants", json!({
+                "id": tid,
+                "name": format!("Tenant {tid}"),
+                "id_strategy": strategy,
+            }));
+            let resp = call_service(&app, req).await;
+            let body = assert_status(resp, StatusCode::CREATED).await;
+            let tenant: Tenant = serde_json::from_slice(&body).unwrap();
+            assert_eq!(tenant.id_strategy.to_string(), strategy);
+            let mut ids = Vec::new();
+            for i in 0..2 {
+                let req = post(&format!("/{tid}"), json!({ "name": format!("Element {i}") }));
+                let resp = call_service(&app, req).await;
+                let body = assert_status(resp, StatusCode::CREATED).await;
+                let el: ElementPayload = serde_json::from_slice(&body).unwrap();
+                let id = el.id.unwrap();
+                assert!(Regex::new(id_regex).unwrap().is_match(&id), "{strategy}: {id}");
+                ids.push(id);
+            }
+            assert_ne!(ids[0], ids[1], "{strategy}");
+            if strategy == "sequence" {
+                assert_eq!(ids, vec!["1", "2"]);
+            }
+        }
+        // The sequence skips the ids set by the clients
+        let tid = format!("ids-sequence-{rand_for_test}");
+        for id in ["3", "4"] {
+            let req = put(&format!("/{tid}/{id}"), json!({ "name": format!("Element {id}") }));
+            assert_status(call_service(&app, req).await, StatusCode::OK).await;
+        }
+        let req = post(&format!("/{tid}"), json!({ "name": "Element 5" }));
+        let body = assert_status(call_service(&app, req).await, StatusCode::CREATED).await;
+        let el: ElementPayload = serde_json::from_slice(&body).unwrap();
+        assert_eq!(el.id.as_deref(), Some("5"));
+        let tid = format!("seq-taken-{rand_for_test}");
+        let req = post("/tenants", json!({"id": tid, "name": format!("Tenant {tid}"), "id_strategy": "sequence"}));
+        assert_status(call_service(&app, req).await, StatusCode::CREATED).await;
+        let req = post(&format!("/{tid}"), json!({ "id": "1" }));
+        assert_status(call_service(&app, req).await, StatusCode::CREATED).await;
+        let req = post(&format!("/{tid}"), json!({ "name": "Auto" }));
+        let body = assert_status(call_service(&app, req).await, StatusCode::CREATED).await;
+        let el: ElementPayload = serde_json::from_slice(&body).unwrap();
+        assert_eq!(el.id.as_deref(), Some("2"));
+        // The same content cannot be inserted twice with the "hash" strategy
+        let req = post(&format!("/ids-hash-{rand_for_test}"), json!({ "name": "Element 0" }));
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::BAD_REQUEST).await;
+        let error: ValidationErrorPayload = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.code, Some("already_exists".to_string()));
+        let req = post("/tenants", json!({
+            "id": format!("ids-wrong-{rand_for_test}"),
+            "name": format!("Tenant wrong {rand_for_test}"),
+            "id_strategy": "not-a-strategy",
+        }));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        Ok(())
+    }
+
     #[actix_web::test]
     async fn test_element_not_found() -> Result<(), Box<dyn Error>> {
         let state = initialize().await;
@@ -297,4 +375,707 @@ mod tests {
         assert_eq!(el.data.get("some"), Some(&json!(some_data_edited)));
         Ok(())
     }
+
+    #[actix_web::test]
+    async fn test_elements_get_filtered() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        for (id, data) in [
+            ("a", json!({ "color": "red", "size": 5, "dims": { "width": 10 } })),
+            ("b", json!({ "color": "red", "size": 12, "dims": { "width": 20 } })),
+            ("c", json!({ "color": "blue", "size": 20, "tag": null })),
+            ("d", json!({ "color": "green", "size": "12" })),
+        ] {
+            let mut data = data;
+            data["id"] = json!(id);
+            let req = post(&format!("/{tid}"), data);
+            let resp = call_service(&app, req).await;
+            assert_status(resp, StatusCode::CREATED).await;
+        }
+        for (query, expected) in [
+            ("filter[color]=red", vec!["a", "b"]),
+            ("filter[color][ne]=red", vec!["c", "d"]),
+            ("filter[color]=red&filter[size][gt]=10", vec!["b"]),
+            ("filter[size][gte]=12", vec!["b", "c"]),
+            ("filter[size][lt]=12", vec!["a"]),
+            ("filter[size]=%2212%22", vec!["d"]),
+            ("filter[dims.width][lte]=10", vec!["a"]),
+            ("filter[dims][exists]=true", vec!["a", "b"]),
+            ("filter[tag][exists]=true", vec!["c"]),
+            ("filter[tag]=null", vec!["c"]),
+            ("filter[color][in]=blue,green", vec!["c", "d"]),
+            ("filter[size][in]=[5,%2212%22]", vec!["a", "d"]),
+        ] {
+            let req = get(&format!("/{tid}?{query}"));
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{query}");
+            let page: Page<ElementPayload> = try_read_body_json(resp).await?;
+            let mut ids: Vec<String> = page.data.into_iter().filter_map(|e| e.id).collect();
+            ids.sort();
+            assert_eq!(ids, expected, "{query}");
+            assert_eq!(page.total, Some(expected.len() as i64), "{query}");
+        }
+        for query in ["filter[size][like]=1", "filter[tag][exists]=yes", "filter[dims..width]=1"] {
+            let req = get(&format!("/{tid}?{query}"));
+            let resp = call_service(&app, req).await;
+            let body = assert_status(resp, StatusCode::BAD_REQUEST).await;
+            let error: ValidationErrorPayload = serde_json::from_slice(&body).unwrap();
+            assert_eq!(error.code, Some("invalid_filter".to_string()), "{query}");
+        }
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_elements_get_sorted() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        for (id, data) in [
+            ("a", json!({ "priority": 2, "meta": { "rank": 3 } })),
+            ("b", json!({ "priority": 1, "meta": { "rank": 1 } })),
+            ("c", json!({ "priority": 2 })),
+            ("d", json!({ "priority": 3, "meta": { "rank": 2 } })),
+            ("e", json!({})),
+        ] {
+            let mut data = data;
+            data["id"] = json!(id);
+            let req = post(&format!("/{tid}"), data);
+            let resp = call_service(&app, req).await;
+            assert_status(resp, StatusCode::CREATED).await;
+        }
+        for (sort, expected) in [
+            ("id", vec!["a", "b", "c", "d", "e"]),
+            ("-id", vec!["e", "d", "c", "b", "a"]),
+            // Ties are sorted by id, and elements without the attribute go last
+            ("data.priority", vec!["b", "a", "c", "d", "e"]),
+            ("-data.priority", vec!["d", "a", "c", "b", "e"]),
+            ("-data.priority,-id", vec!["d", "c", "a", "b", "e"]),
+            ("data.meta.rank", vec!["b", "d", "a", "c", "e"]),
+        ] {
+            let req = get(&format!("/{tid}?sort={sort}"));
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{sort}");
+            let page: Page<ElementPayload> = try_read_body_json(resp).await?;
+            let ids: Vec<String> = page.data.into_iter().filter_map(|e| e.id).collect();
+            assert_eq!(ids, expected, "{sort}");
+        }
+        // Invalid fields are ignored
+        let req = get(&format!("/{tid}?sort=data.priority');--,not_a_field"));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let page: Page<ElementPayload> = try_read_body_json(resp).await?;
+        assert_eq!(page.data.len(), 5);
+        // Pagination with ties is stable
+        let mut ids: Vec<String> = Vec::new();
+        for offset in 0..5 {
+            let req = get(&format!("/{tid}?sort=-data.priority&page_size=1&offset={offset}"));
+            let resp = call_service(&app, req).await;
+            let page: Page<ElementPayload> = try_read_body_json(resp).await?;
+            ids.extend(page.data.into_iter().filter_map(|e| e.id));
+        }
+        assert_eq!(ids, vec!["d", "a", "c", "b", "e"]);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_elements_get_with_cursor() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        for id in ["a", "b", "c", "d", "e"] {
+            let req = post(&format!("/{tid}"), json!({ "id": id }));
+            let resp = call_service(&app, req).await;
+            assert_status(resp, StatusCode::CREATED).await;
+        }
+        let req = get(&format!("/{tid}?page_size=2"));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let page: CursorPage<ElementPayload> = try_read_body_json(resp).await?;
+        let mut ids: Vec<String> = page.page.data.into_iter().filter_map(|e| e.id).collect();
+        let mut next = page.next;
+        // Elements inserted while paginating don't cause duplicates
+        let req = post(&format!("/{tid}"), json!({ "id": "f" }));
+        let resp = call_service(&app, req).await;
+        assert_status(resp, StatusCode::CREATED).await;
+        while let Some(after) = next {
+            let req = get(&format!("/{tid}?page_size=2&after={after}"));
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let page: CursorPage<ElementPayload> = try_read_body_json(resp).await?;
+            assert_eq!(page.page.offset, 0);
+            ids.extend(page.page.data.into_iter().filter_map(|e| e.id));
+            next = page.next;
+        }
+        assert_eq!(ids, vec!["e", "d", "c", "b", "a"]);
+        // The cursor cannot be combined with "sort" or "offset"
+        let req = get(&format!("/{tid}?page_size=2"));
+        let resp = call_service(&app, req).await;
+        let page: CursorPage<ElementPayload> = try_read_body_json(resp).await?;
+        let after = page.next.unwrap();
+        for args in ["sort=id", "offset=2"] {
+            let req = get(&format!("/{tid}?after={after}&{args}"));
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{args}");
+        }
+        // No cursor when sorting by other criteria
+        let req = get(&format!("/{tid}?page_size=2&sort=id"));
+        let resp = call_service(&app, req).await;
+        let page: CursorPage<ElementPayload> = try_read_body_json(resp).await?;
+        assert_eq!(page.next, None);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_elements_search() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        for data in [
+            json!({ "id": "a", "name": "Red Apple", "tags": ["fruit", "red"], "size": 10 }),
+            json!({ "id": "b", "name": "Green apple", "origin": { "city": "Red Hook" } }),
+            json!({ "id": "c", "name": "Application form", "size": 12 }),
+            json!({ "id": "d", "name": "Banana", "tags": ["fruit"] }),
+        ] {
+            let req = post(&format!("/{tid}"), data);
+            let resp = call_service(&app, req).await;
+            assert_status(resp, StatusCode::CREATED).await;
+        }
+        for (q, expected) in [
+            // Prefix matching, ties sorted by creation date
+            ("app", vec!["c", "b", "a"]),
+            // Nested values and numbers are searched too, and
+            // results are sorted by relevance
+            ("red", vec!["a", "b"]),
+            ("red%20apple", vec!["a", "b"]),
+            ("hook", vec!["b"]),
+            ("12", vec!["c"]),
+            ("FRUIT", vec!["d", "a"]),
+            ("pear", vec![]),
+        ] {
+            let req = get(&format!("/{tid}?q={q}"));
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{q}");
+            let page: Page<ElementPayload> = try_read_body_json(resp).await?;
+            let ids: Vec<String> = page.data.into_iter().filter_map(|e| e.id).collect();
+            assert_eq!(ids, expected, "{q}");
+            assert_eq!(page.total, Some(expected.len() as i64), "{q}");
+        }
+        // Search with filters and sorting
+        let req = get(&format!("/{tid}?q=apple&sort=-id&filter[size][exists]=false"));
+        let resp = call_service(&app, req).await;
+        let page: Page<ElementPayload> = try_read_body_json(resp).await?;
+        let ids: Vec<String> = page.data.into_iter().filter_map(|e| e.id).collect();
+        assert_eq!(ids, vec!["b"]);
+        // Symbols are ignored
+        let req = get(&format!("/{tid}?q=!%7C%26:*"));
+        let resp = call_service(&app, req).await;
+        let page: Page<ElementPayload> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(4));
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_elements_bulk() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        let req = post(format!("/{tid}").as_str(), json!({"id": "b0", "name": "To delete"}));
+        assert_status(call_service(&app, req).await, StatusCode::CREATED).await;
+
+        // Atomic mode: nothing is applied if an item fails
+        let items = json!([
+            {"action": "create", "element": {"id": "b1", "name": "One"}},
+            {"action": "delete", "id": "b0"},
+            {"action": "create", "element": {"id": "b1", "name": "Dup"}},
+            {"action": "upsert", "element": {"id": "b2", "name": "Two"}},
+        ]);
+        let resp = call_service(&app, post(format!("/{tid}/_bulk").as_str(), &items)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let result: BulkResult = try_read_body_json(resp).await?;
+        assert!(result.errors);
+        let statuses: Vec<u16> = result.items.iter().map(|i| i.status).collect();
+        assert_eq!(statuses, vec![424, 424, 400, 424]);
+        assert_eq!(result.items[2].error.as_ref().unwrap()["code"], "already_exists");
+        assert_eq!(result.items[3].id.as_deref(), Some("b2"));
+        let resp = call_service(&app, get(format!("/{tid}/b1").as_str())).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = call_service(&app, get(format!("/{tid}/b0").as_str())).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // Best effort mode: the items that fail are skipped
+        let resp = call_service(&app, post(format!("/{tid}/_bulk?mode=best_effort").as_str(), &items)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let result: BulkResult = try_read_body_json(resp).await?;
+        assert!(result.errors);
+        let statuses: Vec<u16> = result.items.iter().map(|i| i.status).collect();
+        assert_eq!(statuses, vec![201, 204, 400, 200]);
+        let resp = call_service(&app, get(format!("/{tid}").as_str())).await;
+        let page: Page<ElementPayload> = try_read_body_json(resp).await?;
+        let mut ids: Vec<String> = page.data.into_iter().filter_map(|e| e.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["b1", "b2"]);
+
+        // NDJSON body, with invalid items
+        let body = [
+            r#"{"action": "upsert", "element": {"id": "b1", "name": "One updated"}}"#,
+            r#"{"action": "delete", "id": "not-found"}"#,
+            r#"{"action": "merge", "id": "b2"}"#,
+            r#"{"action": "create", "element": {"id": "!b3"}}"#,
+            "",
+            r#"{"action": "create", "element": {"name": "Auto id"}}"#,
+        ].join("\n");
+        let req = TestRequest::post()
+            .uri(format!("/{tid}/_bulk?mode=best_effort").as_str())
+            .insert_header(("content-type", "application/x-ndjson"))
+            .set_payload(body)
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let result: BulkResult = try_read_body_json(resp).await?;
+        let statuses: Vec<u16> = result.items.iter().map(|i| i.status).collect();
+        assert_eq!(statuses, vec![200, 404, 400, 400, 201]);
+        assert_eq!(result.items[1].error.as_ref().unwrap()["code"], "not_found");
+        assert_eq!(result.items[2].error.as_ref().unwrap()["code"], "invalid_item");
+        assert!(result.items[3].error.as_ref().unwrap()["field_errors"]["id"].is_array());
+        assert!(result.items[4].id.is_some());
+
+        // Errors of the whole request
+        let resp = call_service(&app, post(format!("/{tid}/_bulk").as_str(), json!({"a": 1}))).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = call_service(&app, post(format!("/{tid}/_bulk").as_str(), json!([]))).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = call_service(&app, post("/not-a-tenant/_bulk", &items)).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // Only the bulk bodies can be larger than the default limit of 256 KiB
+        let name = "x".repeat(300 * 1024);
+        let items = json!([{"action": "upsert", "element": {"id": "b1", "name": name}}]);
+        let resp = call_service(&app, post(format!("/{tid}/_bulk").as_str(), &items)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let req = TestRequest::patch()
+            .uri(format!("/{tid}/b1").as_str())
+            .insert_header(("content-type", "application/merge-patch+json"))
+            .set_payload(json!({"name": name}).to_string())
+            .to_request();
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_elements_mget() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        for id in ["m1", "m2", "m3"] {
+            let req = post(format!("/{tid}").as_str(), json!({"id": id, "name": id}));
+            assert_status(call_service(&app, req).await, StatusCode::CREATED).await;
+        }
+
+        let body = json!({"ids": ["m3", "x1", "m1", "m3", "x1"]});
+        let resp = call_service(&app, post(format!("/{tid}/_mget").as_str(), body)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let found: ElementsFound<ElementPayload> = try_read_body_json(resp).await?;
+        let ids: Vec<String> = found.data.into_iter().filter_map(|e| e.id).collect();
+        assert_eq!(ids, vec!["m3", "m1"]);
+        assert_eq!(found.missing, vec!["x1"]);
+
+        let resp = call_service(&app, post(format!("/{tid}/_mget").as_str(), json!({"ids": []}))).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = call_service(&app, post("/not-a-tenant/_mget", json!({"ids": ["m1"]}))).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_elements_patch() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        let req = post(format!("/{tid}").as_str(), json!({"id": "p1", "name": "One", "tags": ["a"], "dims": {"w": 1, "h": 2}}));
+        let body = assert_status(call_service(&app, req).await, StatusCode::CREATED).await;
+        let created: serde_json::Value = serde_json::from_slice(&body)?;
+        let patch = |content_type: &str, body: serde_json::Value| TestRequest::patch()
+            .uri(format!("/{tid}/p1").as_str())
+            .insert_header(("content-type", content_type))
+            .set_payload(body.to_string())
+            .to_request();
+
+        // JSON Merge Patch
+        let req = patch("application/merge-patch+json", json!({"name": "Uno", "dims": {"h": null, "d": 3}}));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let mut el: serde_json::Value = try_read_body_json(resp).await?;
+        let updated_at = el.as_object_mut().unwrap().remove("updated_at").unwrap();
+        assert!(updated_at.as_str() > created["updated_at"].as_str());
+        assert_eq!(el, json!({
+            "id": "p1", "name": "Uno", "tags": ["a"], "dims": {"w": 1, "d": 3}, "created_at": created["created_at"]
+        }));
+
+        // JSON Patch
+        let ops = json!([
+            {"op": "test", "path": "/name", "value": "Uno"},
+            {"op": "add", "path": "/tags/-", "value": "b"},
+            {"op": "remove", "path": "/dims"},
+        ]);
+        let resp = call_service(&app, patch("application/json-patch+json", ops)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = call_service(&app, get(format!("/{tid}/p1").as_str())).await;
+        let mut el: serde_json::Value = try_read_body_json(resp).await?;
+        el.as_object_mut().unwrap().remove("updated_at");
+        assert_eq!(el, json!({"id": "p1", "name": "Uno", "tags": ["a", "b"], "created_at": created["created_at"]}));
+
+        // Nothing is applied if an operation fails
+        let ops = json!([
+            {"op": "replace", "path": "/name", "value": "Eins"},
+            {"op": "test", "path": "/name", "value": "Uno"},
+        ]);
+        let resp = call_service(&app, patch("application/json-patch+json", ops)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let error: ValidationErrorPayload = try_read_body_json(resp).await?;
+        assert_eq!(error.code.as_deref(), Some("patch_test_failed"));
+        let resp = call_service(&app, get(format!("/{tid}/p1").as_str())).await;
+        let el: serde_json::Value = try_read_body_json(resp).await?;
+        assert_eq!(el["name"], "Uno");
+
+        // Errors
+        let req = patch("application/merge-patch+json", json!({"created_at": "2020-01-01T00:00:00"}));
+        assert_status(call_service(&app, req).await, StatusCode::BAD_REQUEST).await;
+        let req = patch("application/json-patch+json", json!([{"op": "replace", "path": "/id", "value": "p2"}]));
+        assert_status(call_service(&app, req).await, StatusCode::BAD_REQUEST).await;
+        let req = patch("application/json-patch+json", json!({"op": "remove", "path": "/name"}));
+        assert_status(call_service(&app, req).await, StatusCode::BAD_REQUEST).await;
+        let req = patch("application/json", json!({"name": "Uno"}));
+        assert_status(call_service(&app, req).await, StatusCode::UNSUPPORTED_MEDIA_TYPE).await;
+        let req = TestRequest::patch()
+            .uri(format!("/{tid}/not-found").as_str())
+            .insert_header(("content-type", "application/merge-patch+json"))
+            .set_payload("{}")
+            .to_request();
+        assert_status(call_service(&app, req).await, StatusCode::NOT_FOUND).await;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_elements_conditional_requests() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        let uri = format!("/{tid}/c1");
+        let etag = |resp: &actix_web::dev::ServiceResponse| resp.headers().get("etag")
+            .map(|v| v.to_str().unwrap().to_string());
+        let put_if = |header: (&'static str, &str), body: serde_json::Value| TestRequest::put()
+            .uri(uri.as_str())
+            .insert_header(header)
+            .set_json(body)
+            .to_request();
+
+        // Create only if it doesn't exist
+        let resp = call_service(&app, put_if(("if-none-match", "*"), json!({"name": "One", "version": 9}))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(etag(&resp).as_deref(), Some("\"1\""));
+        let el: serde_json::Value = try_read_body_json(resp).await?;
+        assert_eq!(el["version"], 9);
+        let resp = call_service(&app, put_if(("if-none-match", "*"), json!({"name": "Dup"}))).await;
+        assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+        // Conditional GET
+        let resp = call_service(&app, get(uri.as_str())).await;
+        assert_eq!(etag(&resp).as_deref(), Some("\"1\""));
+        let req = TestRequest::get().uri(uri.as_str()).insert_header(("if-none-match", "\"1\"")).to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(etag(&resp).as_deref(), Some("\"1\""));
+
+        // Only one of two updates of the same version succeeds
+        let resp = call_service(&app, put_if(("if-match", "\"1\""), json!({"name": "Uno"}))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(etag(&resp).as_deref(), Some("\"2\""));
+        let resp = call_service(&app, put_if(("if-match", "\"1\""), json!({"name": "One"}))).await;
+        assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+        let error: ValidationErrorPayload = try_read_body_json(resp).await?;
+        assert_eq!(error.code.as_deref(), Some("precondition_failed"));
+        let req = TestRequest::get().uri(uri.as_str()).insert_header(("if-none-match", "\"1\"")).to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let el: serde_json::Value = try_read_body_json(resp).await?;
+        assert_eq!(el["name"], "Uno");
+
+        let req = TestRequest::patch()
+            .uri(uri.as_str())
+            .insert_header(("content-type", "application/merge-patch+json"))
+            .insert_header(("if-match", "\"1\", \"2\""))
+            .set_payload(r#"{"color": "blue"}"#)
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(etag(&resp).as_deref(), Some("\"3\""));
+
+        let req = TestRequest::delete().uri(uri.as_str()).insert_header(("if-match", "\"2\"")).to_request();
+        assert_status(call_service(&app, req).await, StatusCode::PRECONDITION_FAILED).await;
+        let req = TestRequest::delete().uri(uri.as_str()).insert_header(("if-match", "\"3\"")).to_request();
+        assert_status(call_service(&app, req).await, StatusCode::NO_CONTENT).await;
+        let resp = call_service(&app, put_if(("if-match", "*"), json!({"name": "One"}))).await;
+        assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_elements_updated_since() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        for id in ["u1", "u2", "u3"] {
+            let req = post(format!("/{tid}").as_str(), json!({"id": id, "name": id}));
+            assert_status(call_service(&app, req).await, StatusCode::CREATED).await;
+        }
+        let body = assert_status(
+            call_service(&app, put(format!("/{tid}/u3").as_str(), json!({"name": "U3"}))).await,
+            StatusCode::OK,
+        ).await;
+        let u3: serde_json::Value = serde_json::from_slice(&body)?;
+        assert!(u3["updated_at"].as_str() > u3["created_at"].as_str());
+        assert!(u3.get("updated_by").is_none());
+        let req = TestRequest::patch()
+            .uri(format!("/{tid}/u1").as_str())
+            .insert_header(("content-type", "application/merge-patch+json"))
+            .set_payload(r#"{"name": "U1"}"#)
+            .to_request();
+        assert_status(call_service(&app, req).await, StatusCode::OK).await;
+
+        let since = u3["updated_at"].as_str().unwrap();
+        let req = get(format!("/{tid}?updated_since={since}&sort=updated_at").as_str());
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let page: Page<ElementPayload> = try_read_body_json(resp).await?;
+        let ids: Vec<String> = page.data.into_iter().filter_map(|e| e.id).collect();
+        assert_eq!(ids, vec!["u3", "u1"]);
+
+        let req = get(format!("/{tid}?updated_since=yesterday").as_str());
+        assert_status(call_service(&app, req).await, StatusCode::BAD_REQUEST).await;
+        for attr in ["created_at", "updated_at", "updated_by"] {
+            let req = put(format!("/{tid}/u2").as_str(), json!({"name": "U2", attr: "x"}));
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+            let error: ValidationErrorPayload = try_read_body_json(resp).await?;
+            assert_eq!(error.error, format!("cannot provide reserved attribute \"{attr}\""));
+        }
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_elements_revisions() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        let req = post(format!("/{tid}").as_str(), json!({"id": "r1", "name": "v1"}));
+        let body = assert_status(call_service(&app, req).await, StatusCode::CREATED).await;
+        let v1: serde_json::Value = serde_json::from_slice(&body)?;
+        let req = put(format!("/{tid}/r1").as_str(), json!({"name": "v2"}));
+        assert_status(call_service(&app, req).await, StatusCode::OK).await;
+        let req = TestRequest::patch()
+            .uri(format!("/{tid}/r1").as_str())
+            .insert_header(("content-type", "application/merge-patch+json"))
+            .set_payload(r#"{"name": "v3"}"#)
+            .to_request();
+        assert_status(call_service(&app, req).await, StatusCode::OK).await;
+
+        let resp = call_service(&app, get(format!("/{tid}/r1/revisions").as_str())).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let page: Page<serde_json::Value> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(2));
+        assert_eq!(page.data[0]["version"], json!(2));
+        assert_eq!(page.data[0]["element"]["name"], json!("v2"));
+        assert_eq!(page.data[1]["version"], json!(1));
+        assert_eq!(page.data[1]["deleted"], json!(false));
+        assert_eq!(page.data[1]["element"], v1);
+
+        // The element as it was when created, and before it existed
+        let at = v1["updated_at"].as_str().unwrap();
+        let resp = call_service(&app, get(format!("/{tid}/r1?at={at}").as_str())).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("etag").unwrap(), "\"1\"");
+        let el: serde_json::Value = try_read_body_json(resp).await?;
+        assert_eq!(el, v1);
+        let req = get(format!("/{tid}/r1?at=2020-01-01T00:00:00Z").as_str());
+        assert_status(call_service(&app, req).await, StatusCode::NOT_FOUND).await;
+        let req = get(format!("/{tid}/r1?at=yesterday").as_str());
+        assert_status(call_service(&app, req).await, StatusCode::BAD_REQUEST).await;
+
+        let req = post(format!("/{tid}/r1/revisions/1/restore").as_str(), json!({}));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("etag").unwrap(), "\"4\"");
+        let el: serde_json::Value = try_read_body_json(resp).await?;
+        assert_eq!(el["name"], json!("v1"));
+        let req = post(format!("/{tid}/r1/revisions/9/restore").as_str(), json!({}));
+        assert_status(call_service(&app, req).await, StatusCode::NOT_FOUND).await;
+
+        // Deleted elements keep their revisions, and can be restored from them
+        let req = TestRequest::delete().uri(format!("/{tid}/r1").as_str()).to_request();
+        assert_status(call_service(&app, req).await, StatusCode::NO_CONTENT).await;
+        let resp = call_service(&app, get(format!("/{tid}/r1/revisions?page_size=1").as_str())).await;
+        let page: Page<serde_json::Value> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(4));
+        assert_eq!(page.data[0]["version"], json!(4));
+        assert_eq!(page.data[0]["deleted"], json!(true));
+        let req = post(format!("/{tid}/r1/revisions/2/restore").as_str(), json!({}));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("etag").unwrap(), "\"5\"");
+        let el: serde_json::Value = try_read_body_json(resp).await?;
+        assert_eq!(el["name"], json!("v2"));
+        assert_eq!(el["created_at"], el["updated_at"]);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_elements_soft_delete_and_restore() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        for id in ["s1", "s2"] {
+            let req = post(format!("/{tid}").as_str(), json!({"id": id, "name": id}));
+            assert_status(call_service(&app, req).await, StatusCode::CREATED).await;
+        }
+        let req = TestRequest::delete().uri(format!("/{tid}/s1?soft=true").as_str()).to_request();
+        assert_status(call_service(&app, req).await, StatusCode::NO_CONTENT).await;
+        let req = TestRequest::delete().uri(format!("/{tid}/s1?soft=true").as_str()).to_request();
+        assert_status(call_service(&app, req).await, StatusCode::NOT_FOUND).await;
+        let req = TestRequest::delete().uri(format!("/{tid}/s2?soft=true&force=true").as_str()).to_request();
+        assert_status(call_service(&app, req).await, StatusCode::BAD_REQUEST).await;
+
+        // Elements in the trash are only responded if asked for
+        assert_status(call_service(&app, get(format!("/{tid}/s1").as_str())).await, StatusCode::NOT_FOUND).await;
+        let resp = call_service(&app, get(format!("/{tid}/s1?include_deleted=true").as_str())).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let el: serde_json::Value = try_read_body_json(resp).await?;
+        assert!(el["deleted_at"].is_string());
+        let (updated_at, deleted_at) = (el["updated_at"].as_str().unwrap().to_string(), el["deleted_at"].as_str().unwrap().to_string());
+        let page: Page<ElementPayload> = try_read_body_json(call_service(&app, get(format!("/{tid}").as_str())).await).await?;
+        assert_eq!(page.total, Some(1));
+        let req = get(format!("/{tid}?include_deleted=true").as_str());
+        let page: Page<ElementPayload> = try_read_body_json(call_service(&app, req).await).await?;
+        assert_eq!(page.total, Some(2));
+        let req = TestRequest::patch()
+            .uri(format!("/{tid}/s1").as_str())
+            .insert_header(("content-type", "application/merge-patch+json"))
+            .set_payload(r#"{"name": "S1"}"#)
+            .to_request();
+        assert_status(call_service(&app, req).await, StatusCode::NOT_FOUND).await;
+        // Their ids cannot be reused until they are purged
+        let req = post(format!("/{tid}").as_str(), json!({"id": "s1"}));
+        assert_status(call_service(&app, req).await, StatusCode::BAD_REQUEST).await;
+
+        // Moving to the trash and restoring are new versions, validated again
+        let req = put(format!("/tenants/{tid}/schema").as_str(), json!({"required": ["title"]}));
+        assert_status(call_service(&app, req).await, StatusCode::OK).await;
+        let req = post(format!("/{tid}/s1/restore").as_str(), json!({}));
+        let body = assert_status(call_service(&app, req).await, StatusCode::BAD_REQUEST).await;
+        let error: ValidationErrorPayload = serde_json::from_slice(&body)?;
+        assert_eq!(error.code, Some("schema_validation".to_string()));
+        let req = TestRequest::delete().uri(format!("/tenants/{tid}/schema").as_str()).to_request();
+        assert_status(call_service(&app, req).await, StatusCode::NO_CONTENT).await;
+        let req = post(format!("/{tid}/s1/restore").as_str(), json!({}));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("etag").unwrap(), "\"3\"");
+        let el: serde_json::Value = try_read_body_json(resp).await?;
+        assert_eq!(el["name"], json!("s1"));
+        let resp = call_service(&app, get(format!("/{tid}/s1/revisions").as_str())).await;
+        let page: Page<serde_json::Value> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(2));
+        assert_eq!(page.data[0]["deleted"], json!(true));
+        assert_eq!(page.data[1]["deleted"], json!(false));
+        assert!(el.get("deleted_at").is_none());
+        // It's read as it was before and after the time in the trash, but not within
+        let restored_at = page.data[0]["replaced_at"].as_str().unwrap();
+        for (at, status) in [(updated_at.as_str(), StatusCode::OK), (deleted_at.as_str(), StatusCode::NOT_FOUND), (restored_at, StatusCode::OK)] {
+            let resp = call_service(&app, get(format!("/{tid}/s1?at={at}").as_str())).await;
+            assert_eq!(resp.status(), status, "at {at}");
+        }
+        let req = post(format!("/{tid}/s1/restore").as_str(), json!({}));
+        assert_status(call_service(&app, req).await, StatusCode::NOT_FOUND).await;
+
+        // A PUT restores the element too, with the new attributes
+        let req = TestRequest::delete().uri(format!("/{tid}/s2?soft=true").as_str()).to_request();
+        assert_status(call_service(&app, req).await, StatusCode::NO_CONTENT).await;
+        let req = put(format!("/{tid}/s2").as_str(), json!({"name": "S2"}));
+        assert_status(call_service(&app, req).await, StatusCode::OK).await;
+        let resp = call_service(&app, get(format!("/{tid}/s2").as_str())).await;
+        let el: serde_json::Value = try_read_body_json(resp).await?;
+        assert_eq!(el["name"], json!("S2"));
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_elements_attributes_named_as_server_fields() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        // Attributes of elements created before the server set "updated_at" and "updated_by"
+        let mut conn = state.get_conn().await?;
+        let mut tx = Connection::begin(&mut conn).await?;
+        sqlx::query(
+                r#"
+            INSERT INTO elements (tid, id, data, created_at, updated_at)
+            VALUES ($1, 'legacy', '{"name": "L", "updated_at": "yesterday", "updated_by": "me", "deleted_at": "never"}', NOW(), NOW())
+                "#
+            )
+            .bind(tid.to_string())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        let app = init_service(App::new().configure(AppServer::config_app(state.clone()))).await;
+        let resp = call_service(&app, get(format!("/{tid}/legacy").as_str())).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = String::from_utf8(read_body(resp).await.to_vec())?;
+        assert_eq!(body.matches("\"updated_at\"").count(), 1);
+        let el: serde_json::Value = serde_json::from_str(&body)?;
+        assert_ne!(el["updated_at"], "yesterday");
+        // Only responded while the server doesn't set the field
+        assert_eq!(el["updated_by"], "me");
+        assert_eq!(el["deleted_at"], "never");
+        let req = TestRequest::delete().uri(format!("/{tid}/legacy?soft=true").as_str()).to_request();
+        assert_status(call_service(&app, req).await, StatusCode::NO_CONTENT).await;
+        let resp = call_service(&app, get(format!("/{tid}/legacy?include_deleted=true").as_str())).await;
+        let body = String::from_utf8(read_body(resp).await.to_vec())?;
+        assert_eq!(body.matches("\"deleted_at\"").count(), 1);
+        let el: serde_json::Value = serde_json::from_str(&body)?;
+        assert_ne!(el["deleted_at"], "never");
+        // The attributes are kept as they are
+        let mut tx = Connection::begin(&mut conn).await?;
+        let el = Element::get(&mut tx, tid.to_string().as_str(), "legacy", true).await?.unwrap();
+        assert_eq!(el.data["updated_at"], "yesterday");
+        assert_eq!(el.data["deleted_at"], "never");
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_elements_forbidden_id() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        let req = post(format!("/{tid}").as_str(), json!({"id": "sets", "name": "Sets"}));
+        assert_status(call_service(&app, req).await, StatusCode::BAD_REQUEST).await;
+        let req = put(format!("/{tid}/sets").as_str(), json!({"name": "Sets"}));
+        let body = assert_status(call_service(&app, req).await, StatusCode::BAD_REQUEST).await;
+        let error: ValidationErrorPayload = serde_json::from_slice(&body)?;
+        assert_eq!(error.code.as_deref(), Some("forbidden_id"));
+        let req = TestRequest::patch()
+            .uri(format!("/{tid}/sets").as_str())
+            .insert_header(("content-type", "application/merge-patch+json"))
+            .set_payload(r#"{"name": "Sets"}"#)
+            .to_request();
+        assert_status(call_service(&app, req).await, StatusCode::BAD_REQUEST).await;
+        let items = json!([{"action": "upsert", "element": {"id": "sets", "name": "Sets"}}]);
+        let resp = call_service(&app, post(format!("/{tid}/_bulk").as_str(), &items)).await;
+        let result: BulkResult = try_read_body_json(resp).await?;
+        assert_eq!(result.items[0].status, 400);
+        // The set listing is still responded
+        let resp = call_service(&app, get(format!("/{tid}/sets").as_str())).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let page: Page<serde_json::Value> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(0));
+        Ok(())
+    }
 }
@@ -15,8 +15,14 @@ use sqlx::Connection;
 use std::process::exit;
 use std::sync::{Arc, LazyLock, Once};
 
+mod auth_api_tests;
 mod health_api_tests;
+mod metrics_api_tests;
 mod elements_api_tests;
+mod relationships_api_tests;
+mod schemas_api_tests;
+mod sets_api_tests;
+mod telemetry_api_tests;
 mod tenants_api_tests;
 
 static INIT: Once = Once::new();
@@ -61,6 +67,7 @@ pub async fn create_tenant(state: &Data<AppState>) -> u16 {
     let tenant = TenantPayload {
         id: tid.to_string(),
         name: format!("{tid} API"),
+        id_strategy: None,
     };
     let mut conn = state.get_conn().await.unwrap_or_else(|error| {
         error!("{error}");
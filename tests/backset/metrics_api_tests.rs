@@ -0,0 +1,47 @@
+#[cfg(test)]
+mod tests {
+    use crate::{create_tenant, get, initialize, post};
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::App;
+    use backset::app_server::AppServer;
+    use backset::metrics::metrics_middleware;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use std::error::Error;
+
+    #[actix_web::test]
+    async fn test_metrics_get() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(
+            App::new()
+                .configure(AppServer::config_app(state))
+                .wrap(from_fn(metrics_middleware))
+        ).await;
+        let resp = call_service(&app, post(format!("/{tid}").as_str(), json!({"id": "m1"}))).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let resp = call_service(&app, post(format!("/{tid}").as_str(), json!({"id": "m2"}))).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let resp = call_service(&app, get(format!("/{tid}/m1").as_str())).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let req = TestRequest::delete().uri(format!("/{tid}/m1").as_str()).to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let resp = call_service(&app, get("/metrics")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get("content-type").unwrap().to_str()?.starts_with("text/plain"));
+        let body = String::from_utf8(read_body(resp).await.to_vec())?;
+        // Requests are labelled by the route pattern, not the tenant or element ids
+        assert!(body.contains(r#"backset_http_requests_total{method="POST",path="/{tid}",status="201"}"#));
+        assert!(body.contains(r#"backset_http_request_duration_seconds_count{method="GET",path="/{tid}/{id}"}"#));
+        assert!(!body.contains(format!("path=\"/{tid}").as_str()));
+        assert!(body.contains(format!("backset_elements_created_total{{tenant=\"{tid}\"}} 2").as_str()));
+        assert!(body.contains(format!("backset_elements_deleted_total{{tenant=\"{tid}\"}} 1").as_str()));
+        assert!(body.contains("backset_db_pool_size "));
+        assert!(body.contains("backset_db_pool_idle "));
+        Ok(())
+    }
+}
@@ -0,0 +1,282 @@
+#[cfg(test)]
+mod tests {
+    use crate::{get, post, create_tenant, initialize};
+    use actix_contrib_rest::page::Page;
+    use actix_contrib_rest::result::{DeletedCount, ValidationErrorPayload};
+    use actix_contrib_rest::test::assert_status;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, try_read_body_json, TestRequest};
+    use actix_web::App;
+    use actix_web::dev::{Service, ServiceResponse};
+    use actix_http::Request;
+    use backset::app_server::AppServer;
+    use backset::relationships::model::{ReachedElement, Relationship, TraversePath};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use std::error::Error;
+
+    #[actix_web::test]
+    async fn test_relationships_post_list_and_delete() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        for id in ["a", "b", "c"] {
+            let req = post(&format!("/{tid}"), json!({ "id": id }));
+            let resp = call_service(&app, req).await;
+            assert_status(resp, StatusCode::CREATED).await;
+        }
+        let req = post(&format!("/{tid}/a/relationships"), json!({
+            "rel_type": "depends_on",
+            "to_id": "b",
+            "data": { "weight": 2 },
+        }));
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::CREATED).await;
+        let rel: Relationship = serde_json::from_slice(&body).unwrap();
+        assert_eq!(rel.from_id, "a");
+        assert_eq!(rel.rel_type, "depends_on");
+        assert_eq!(rel.to_id, "b");
+        assert_eq!(rel.data.get("weight"), Some(&json!(2)));
+        for (from, rel_type, to) in [("a", "owns", "c"), ("c", "depends_on", "b")] {
+            let req = post(
+                &format!("/{tid}/{from}/relationships"),
+                json!({ "rel_type": rel_type, "to_id": to })
+            );
+            let resp = call_service(&app, req).await;
+            assert_status(resp, StatusCode::CREATED).await;
+        }
+        // Outgoing relationships by default
+        let req = get(&format!("/{tid}/a/relationships"));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let page: Page<Relationship> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(2));
+        let req = get(&format!("/{tid}/a/relationships?rel_type=owns"));
+        let resp = call_service(&app, req).await;
+        let page: Page<Relationship> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(1));
+        assert_eq!(page.data[0].to_id, "c");
+        let req = get(&format!("/{tid}/b/relationships?direction=in"));
+        let resp = call_service(&app, req).await;
+        let page: Page<Relationship> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(2));
+        assert!(page.data.iter().all(|r| r.to_id == "b" && r.rel_type == "depends_on"));
+        let req = TestRequest::delete()
+            .uri(&format!("/{tid}/a/relationships/owns/c"))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let req = TestRequest::delete()
+            .uri(&format!("/{tid}/a/relationships/owns/c"))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_relationships_post_errors() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        for id in ["a", "b"] {
+            let req = post(&format!("/{tid}"), json!({ "id": id }));
+            let resp = call_service(&app, req).await;
+            assert_status(resp, StatusCode::CREATED).await;
+        }
+        let req = post(
+            &format!("/{tid}/a/relationships"),
+            json!({ "rel_type": "knows", "to_id": "does-not-exist" })
+        );
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::NOT_FOUND).await;
+        let error: ValidationErrorPayload = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            error.error,
+            "element with id equals to \"does-not-exist\" not found or was removed"
+        );
+        let req = post(
+            &format!("/{tid}/a/relationships"),
+            json!({ "rel_type": "not valid", "to_id": "b" })
+        );
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let req = post(&format!("/{tid}/a/relationships"), json!({ "rel_type": "knows", "to_id": "b" }));
+        let resp = call_service(&app, req).await;
+        assert_status(resp, StatusCode::CREATED).await;
+        let req = post(&format!("/{tid}/a/relationships"), json!({ "rel_type": "knows", "to_id": "b" }));
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::BAD_REQUEST).await;
+        let error: ValidationErrorPayload = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.code, Some("already_exists".to_string()));
+        assert_eq!(error.error, "relationship \"knows\" from \"a\" to \"b\" already exists");
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_elements_delete_with_relationships() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        for id in ["a", "b"] {
+            let req = post(&format!("/{tid}"), json!({ "id": id }));
+            let resp = call_service(&app, req).await;
+            assert_status(resp, StatusCode::CREATED).await;
+        }
+        let req = post(&format!("/{tid}/a/relationships"), json!({ "rel_type": "knows", "to_id": "b" }));
+        let resp = call_service(&app, req).await;
+        assert_status(resp, StatusCode::CREATED).await;
+        // The target of the relationship cannot be deleted either
+        let req = TestRequest::delete().uri(&format!("/{tid}/b")).to_request();
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::BAD_REQUEST).await;
+        let error: ValidationErrorPayload = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.error, "cannot delete element with relationships");
+        let req = TestRequest::delete().uri(&format!("/{tid}/b?force=true")).to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let req = get(&format!("/{tid}/a/relationships"));
+        let resp = call_service(&app, req).await;
+        let page: Page<Relationship> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(0));
+        // The tenant can be deleted with its elements and relationships
+        let req = post(&format!("/{tid}/a/relationships"), json!({ "rel_type": "self", "to_id": "a" }));
+        let resp = call_service(&app, req).await;
+        assert_status(resp, StatusCode::CREATED).await;
+        let req = TestRequest::delete().uri(&format!("/tenants/{tid}?force=true")).to_request();
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::OK).await;
+        let deleted_count: DeletedCount = serde_json::from_slice(&body).unwrap();
+        assert_eq!(deleted_count.deleted, 3);
+        Ok(())
+    }
+
+    /// Create a tenant with the graph: a -> b -> c -> a, a -> d -> c,
+    /// all relationships of type "depends_on", and c -"owns"-> e.
+    async fn create_graph(
+        app: &impl Service<Request, Response = ServiceResponse, Error = actix_web::Error>,
+        tid: u16,
+    ) {
+        for id in ["a", "b", "c", "d", "e", "f"] {
+            let req = post(&format!("/{tid}"), json!({ "id": id }));
+            let resp = call_service(app, req).await;
+            assert_status(resp, StatusCode::CREATED).await;
+        }
+        for (from, rel_type, to) in [
+            ("a", "depends_on", "b"),
+            ("b", "depends_on", "c"),
+            ("c", "depends_on", "a"),
+            ("a", "depends_on", "d"),
+            ("d", "depends_on", "c"),
+            ("c", "owns", "e"),
+        ] {
+            let req = post(
+                &format!("/{tid}/{from}/relationships"),
+                json!({ "rel_type": rel_type, "to_id": to })
+            );
+            let resp = call_service(app, req).await;
+            assert_status(resp, StatusCode::CREATED).await;
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_traverse_reachable() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        create_graph(&app, tid).await;
+        let req = get(&format!("/{tid}/a/traverse"));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let page: Page<ReachedElement> = try_read_body_json(resp).await?;
+        let reached: Vec<(&str, i32)> = page.data.iter()
+            .map(|e| (e.id.as_str(), e.depth))
+            .collect();
+        assert_eq!(reached, vec![("b", 1), ("d", 1), ("c", 2), ("e", 3)]);
+        let req = get(&format!("/{tid}/a/traverse?rel_type=depends_on&max_depth=1"));
+        let resp = call_service(&app, req).await;
+        let page: Page<ReachedElement> = try_read_body_json(resp).await?;
+        let reached: Vec<&str> = page.data.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(reached, vec!["b", "d"]);
+        let req = get(&format!("/{tid}/e/traverse?direction=in&limit=2"));
+        let resp = call_service(&app, req).await;
+        let page: Page<ReachedElement> = try_read_body_json(resp).await?;
+        let reached: Vec<&str> = page.data.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(reached, vec!["c", "b"]);
+        // Depth guard
+        let req = get(&format!("/{tid}/a/traverse?max_depth=1000"));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_traverse_path_and_cycle() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        create_graph(&app, tid).await;
+        let req = get(&format!("/{tid}/a/traverse?mode=path&to=e"));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let path: TraversePath = try_read_body_json(resp).await?;
+        assert_eq!(path.path, Some(vec!["a".into(), "b".into(), "c".into(), "e".into()]));
+        let req = get(&format!("/{tid}/a/traverse?mode=path&to=e&rel_type=depends_on"));
+        let resp = call_service(&app, req).await;
+        let path: TraversePath = try_read_body_json(resp).await?;
+        assert_eq!(path.path, None);
+        let req = get(&format!("/{tid}/a/traverse?mode=path&to=f"));
+        let resp = call_service(&app, req).await;
+        let path: TraversePath = try_read_body_json(resp).await?;
+        assert_eq!(path.path, None);
+        let req = get(&format!("/{tid}/a/traverse?mode=path"));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let req = get(&format!("/{tid}/d/traverse?mode=cycle"));
+        let resp = call_service(&app, req).await;
+        let path: TraversePath = try_read_body_json(resp).await?;
+        assert_eq!(path.path, Some(vec!["d".into(), "c".into(), "a".into(), "d".into()]));
+        let req = get(&format!("/{tid}/e/traverse?mode=cycle"));
+        let resp = call_service(&app, req).await;
+        let path: TraversePath = try_read_body_json(resp).await?;
+        assert_eq!(path.path, None);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_relationships_skip_elements_in_trash() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        create_graph(&app, tid).await;
+        let req = TestRequest::delete().uri(&format!("/{tid}/b?soft=true")).to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let req = get(&format!("/{tid}/a/relationships"));
+        let resp = call_service(&app, req).await;
+        let page: Page<Relationship> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(1));
+        assert_eq!(page.data[0].to_id, "d");
+        let req = get(&format!("/{tid}/c/relationships?direction=in"));
+        let resp = call_service(&app, req).await;
+        let page: Page<Relationship> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(1));
+        assert_eq!(page.data[0].from_id, "d");
+        let req = get(&format!("/{tid}/a/traverse"));
+        let resp = call_service(&app, req).await;
+        let page: Page<ReachedElement> = try_read_body_json(resp).await?;
+        let reached: Vec<(&str, i32)> = page.data.iter()
+            .map(|e| (e.id.as_str(), e.depth))
+            .collect();
+        assert_eq!(reached, vec![("d", 1), ("c", 2), ("e", 3)]);
+        // Restored, the element is reachable again
+        let req = post(&format!("/{tid}/b/restore"), json!({}));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let req = get(&format!("/{tid}/a/relationships"));
+        let resp = call_service(&app, req).await;
+        let page: Page<Relationship> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(2));
+        Ok(())
+    }
+}
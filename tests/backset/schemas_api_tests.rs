@@ -0,0 +1,130 @@
+#[cfg(test)]
+mod tests {
+    use crate::{get, post, put, create_tenant, initialize};
+    use actix_contrib_rest::result::ValidationErrorPayload;
+    use actix_contrib_rest::test::assert_status;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, try_read_body_json, TestRequest};
+    use actix_web::App;
+    use backset::app_server::AppServer;
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, Value};
+    use std::error::Error;
+
+    #[actix_web::test]
+    async fn test_tenant_schema() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        let req = get(&format!("/tenants/{tid}/schema"));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let req = put(&format!("/tenants/{tid}/schema"), json!({ "type": "not-a-type" }));
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::BAD_REQUEST).await;
+        let error: ValidationErrorPayload = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.code, Some("invalid_schema".to_string()));
+        let schema = json!({
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": { "type": "string" },
+                "dims": {
+                    "type": "object",
+                    "properties": { "width": { "type": "number", "minimum": 1 } }
+                }
+            }
+        });
+        let req = put(&format!("/tenants/{tid}/schema"), &schema);
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let req = get(&format!("/tenants/{tid}/schema"));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let saved: Value = try_read_body_json(resp).await?;
+        assert_eq!(saved, schema);
+        for (data, error_msg) in [
+            (json!({ "id": "a" }), "invalid element: \"name\" is a required property"),
+            (
+                json!({ "id": "a", "name": "A", "dims": { "width": 0 } }),
+                "invalid attribute \"/dims/width\": 0 is less than the minimum of 1",
+            ),
+        ] {
+            let req = post(&format!("/{tid}"), data);
+            let resp = call_service(&app, req).await;
+            let body = assert_status(resp, StatusCode::BAD_REQUEST).await;
+            let error: ValidationErrorPayload = serde_json::from_slice(&body).unwrap();
+            assert_eq!(error.code, Some("schema_validation".to_string()));
+            assert_eq!(error.error, error_msg);
+        }
+        let req = post(&format!("/{tid}"), json!({ "id": "a", "name": "A", "dims": { "width": 2 } }));
+        let resp = call_service(&app, req).await;
+        assert_status(resp, StatusCode::CREATED).await;
+        let req = put(&format!("/{tid}/a"), json!({ "name": 1 }));
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::BAD_REQUEST).await;
+        let error: ValidationErrorPayload = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.error, "invalid attribute \"/name\": 1 is not of type \"string\"");
+        // Without the schema any attribute is accepted
+        let req = TestRequest::delete().uri(&format!("/tenants/{tid}/schema")).to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let req = put(&format!("/{tid}/a"), json!({ "name": 1 }));
+        let resp = call_service(&app, req).await;
+        assert_status(resp, StatusCode::OK).await;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_set_schema() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        for data in [json!({ "id": "a", "color": "red" }), json!({ "id": "b", "color": 1 })] {
+            let req = post(&format!("/{tid}"), data);
+            let resp = call_service(&app, req).await;
+            assert_status(resp, StatusCode::CREATED).await;
+        }
+        let req = post(&format!("/{tid}/sets"), json!({ "id": "colors", "name": "Colors" }));
+        let resp = call_service(&app, req).await;
+        assert_status(resp, StatusCode::CREATED).await;
+        let schema = json!({ "properties": { "color": { "type": "string" } } });
+        let req = put(&format!("/{tid}/sets/not-found/schema"), &schema);
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let req = put(&format!("/{tid}/sets/colors/schema"), &schema);
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        // Only the elements that match the schema can be added
+        let req = post(&format!("/{tid}/sets/colors/elements"), json!({ "ids": ["a", "b"] }));
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::BAD_REQUEST).await;
+        let error: ValidationErrorPayload = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.code, Some("schema_validation".to_string()));
+        assert_eq!(
+            error.error,
+            "element \"b\": invalid attribute \"/color\": 1 is not of type \"string\""
+        );
+        let req = post(&format!("/{tid}/sets/colors/elements"), json!({ "ids": ["a"] }));
+        let resp = call_service(&app, req).await;
+        assert_status(resp, StatusCode::OK).await;
+        // The schema of the set applies to its elements, but not to the rest
+        let req = put(&format!("/{tid}/a"), json!({ "color": 2 }));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let req = put(&format!("/{tid}/b"), json!({ "color": 2 }));
+        let resp = call_service(&app, req).await;
+        assert_status(resp, StatusCode::OK).await;
+        // The schema is removed along with the set
+        let req = TestRequest::delete().uri(&format!("/{tid}/sets/colors")).to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let req = get(&format!("/{tid}/sets/colors/schema"));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let req = put(&format!("/{tid}/a"), json!({ "color": 2 }));
+        let resp = call_service(&app, req).await;
+        assert_status(resp, StatusCode::OK).await;
+        Ok(())
+    }
+}
@@ -0,0 +1,350 @@
+#[cfg(test)]
+mod tests {
+    use crate::{get, post, put, create_tenant, initialize, initialize_tenant};
+    use actix_contrib_rest::page::Page;
+    use actix_contrib_rest::result::{DeletedCount, ValidationErrorPayload};
+    use actix_contrib_rest::test::assert_status;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, try_read_body_json, TestRequest};
+    use actix_web::App;
+    use actix_web::dev::{Service, ServiceResponse};
+    use actix_http::Request;
+    use backset::app_server::AppServer;
+    use backset::elements::model::ElementPayload;
+    use backset::sets::model::{AddedCount, Set};
+    use pretty_assertions::assert_eq;
+    use rand::random;
+    use serde_json::json;
+    use std::error::Error;
+
+    #[actix_web::test]
+    async fn test_sets_post_and_get() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tids: &(u16, u16, u16) = initialize_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        let _id = random::<u32>();
+        let id = format!("set-{_id}");
+        let name = format!("Set {_id}");
+        let req = post(&format!("/{}/sets", tids.0), json!({ "id": id, "name": name }));
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::CREATED).await;
+        let set: Set = serde_json::from_slice(&body).unwrap();
+        assert_eq!(set.id, id);
+        assert_eq!(set.name, name);
+        let req = get(&format!("/{}/sets/{}", tids.0, id));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let set: Set = try_read_body_json(resp).await?;
+        assert_eq!(set.id, id);
+        assert_eq!(set.name, name);
+        // The set is not visible from another tenant
+        let req = get(&format!("/{}/sets/{}", tids.1, id));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_sets_post_already_exists() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tids: &(u16, u16, u16) = initialize_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        let id = format!("first-set-{}", random::<u32>());
+        let req = post(&format!("/{}/sets", tids.1), json!({ "id": id, "name": "First" }));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let req = post(&format!("/{}/sets", tids.1), json!({ "id": id, "name": "Second" }));
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::BAD_REQUEST).await;
+        let error: ValidationErrorPayload = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.code, Some("already_exists".to_string()));
+        assert_eq!(error.error, format!("set with id \"{id}\" already exists"));
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_sets_put_delete_and_list() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        for i in 0..3 {
+            let req = put(&format!("/{tid}/sets/set-{i}"), json!({ "name": format!("Set {i}") }));
+            let resp = call_service(&app, req).await;
+            assert_status(resp, StatusCode::OK).await;
+        }
+        let req = put(&format!("/{tid}/sets/set-2"), json!({ "name": "Set 2 edited" }));
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::OK).await;
+        let set: Set = serde_json::from_slice(&body).unwrap();
+        assert_eq!(set.name, "Set 2 edited");
+        let req = get(&format!("/{tid}/sets?sort=-id"));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let page: Page<Set> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(3));
+        assert_eq!(page.data[0].id, "set-2");
+        let req = get(&format!("/{tid}/sets?q=edited"));
+        let resp = call_service(&app, req).await;
+        let page: Page<Set> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(1));
+        let req = TestRequest::delete().uri(&format!("/{tid}/sets/set-2")).to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let req = get(&format!("/{tid}/sets/set-2"));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_sets_elements_membership() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        for i in 0..5 {
+            let req = post(&format!("/{tid}"), json!({ "id": format!("el-{i}") }));
+            let resp = call_service(&app, req).await;
+            assert_status(resp, StatusCode::CREATED).await;
+        }
+        let req = post(&format!("/{tid}/sets"), json!({ "id": "members", "name": "Members" }));
+        let resp = call_service(&app, req).await;
+        assert_status(resp, StatusCode::CREATED).await;
+        let req = post(
+            &format!("/{tid}/sets/members/elements"),
+            json!({ "ids": ["el-0", "el-1", "el-2"] })
+        );
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::OK).await;
+        let added: AddedCount = serde_json::from_slice(&body).unwrap();
+        assert_eq!(added.added, 3);
+        // Elements already in the set are ignored
+        let req = post(
+            &format!("/{tid}/sets/members/elements"),
+            json!({ "ids": ["el-2", "el-3"] })
+        );
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::OK).await;
+        let added: AddedCount = serde_json::from_slice(&body).unwrap();
+        assert_eq!(added.added, 1);
+        // Elements that don't exist cannot be added
+        let req = post(
+            &format!("/{tid}/sets/members/elements"),
+            json!({ "ids": ["el-4", "el-does-not-exist"] })
+        );
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::NOT_FOUND).await;
+        let error: ValidationErrorPayload = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.code, Some("not_found".to_string()));
+        let req = get(&format!("/{tid}/sets/members/elements?page_size=2"));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let page: Page<String> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(4));
+        assert_eq!(page.page_size, 2);
+        let req = TestRequest::delete()
+            .uri(&format!("/{tid}/sets/members/elements/el-0"))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        // Deleting an element removes it from the set as well
+        let req = TestRequest::delete().uri(&format!("/{tid}/el-1")).to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let req = get(&format!("/{tid}/sets/members/elements"));
+        let resp = call_service(&app, req).await;
+        let mut page: Page<String> = try_read_body_json(resp).await?;
+        page.data.sort();
+        assert_eq!(page.data, vec!["el-2", "el-3"]);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_sets_elements_set_not_found() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tids: &(u16, u16, u16) = initialize_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        let req = get(&format!("/{}/sets/does-not-exist-{}/elements", tids.2, random::<u32>()));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_tenants_delete_with_sets() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        let req = post(&format!("/{tid}/sets"), json!({ "name": "To be deleted" }));
+        let resp = call_service(&app, req).await;
+        assert_status(resp, StatusCode::CREATED).await;
+        let req = TestRequest::delete().uri(&format!("/tenants/{tid}")).to_request();
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::BAD_REQUEST).await;
+        let error: ValidationErrorPayload = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.error, "cannot delete tenant with sets");
+        let req = TestRequest::delete().uri(&format!("/tenants/{tid}?force=true")).to_request();
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::OK).await;
+        let deleted_count: DeletedCount = serde_json::from_slice(&body).unwrap();
+        assert_eq!(deleted_count.deleted, 2);
+        Ok(())
+    }
+
+    /// Create a tenant with the elements "el-0" to "el-5" and the sets:
+    /// "a" = {0, 1, 2, 3}, "b" = {2, 3, 4} and "c" = {3, 4, 5}.
+    async fn create_sets(
+        app: &impl Service<Request, Response = ServiceResponse, Error = actix_web::Error>,
+        tid: u16,
+    ) {
+        for i in 0..6 {
+            let req = post(&format!("/{tid}"), json!({ "id": format!("el-{i}"), "num": i }));
+            let resp = call_service(app, req).await;
+            assert_status(resp, StatusCode::CREATED).await;
+        }
+        for (sid, ids) in [("a", [0, 1, 2, 3].as_slice()), ("b", &[2, 3, 4]), ("c", &[3, 4, 5])] {
+            let req = post(&format!("/{tid}/sets"), json!({ "id": sid, "name": sid }));
+            let resp = call_service(app, req).await;
+            assert_status(resp, StatusCode::CREATED).await;
+            let ids: Vec<String> = ids.iter().map(|i| format!("el-{i}")).collect();
+            let req = post(&format!("/{tid}/sets/{sid}/elements"), json!({ "ids": ids }));
+            let resp = call_service(app, req).await;
+            assert_status(resp, StatusCode::OK).await;
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_sets_operations() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        create_sets(&app, tid).await;
+        for (op, sets, expected) in [
+            ("union", vec!["a", "b"], vec!["el-0", "el-1", "el-2", "el-3", "el-4"]),
+            ("intersection", vec!["a", "b", "c"], vec!["el-3"]),
+            ("intersection", vec!["a", "b", "b"], vec!["el-2", "el-3"]),
+            ("difference", vec!["a", "c"], vec!["el-0", "el-1", "el-2"]),
+            ("difference", vec!["c", "a", "b"], vec!["el-5"]),
+            ("difference", vec!["a", "b", "a"], vec![]),
+            ("difference", vec!["a", "c", "c"], vec!["el-0", "el-1", "el-2"]),
+            ("symmetric_difference", vec!["a", "b"], vec!["el-0", "el-1", "el-4"]),
+            ("symmetric_difference", vec!["a", "b", "c"], vec!["el-0", "el-1", "el-3", "el-5"]),
+        ] {
+            let req = post(
+                &format!("/{tid}/sets/_ops"),
+                json!({ "op": op, "sets": sets, "ids_only": true })
+            );
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let page: Page<String> = try_read_body_json(resp).await?;
+            assert_eq!(page.data, expected, "{op} of {sets:?}");
+            assert_eq!(page.total, Some(expected.len() as i64));
+        }
+        let req = post(
+            &format!("/{tid}/sets/_ops?page_size=2"),
+            json!({ "op": "union", "sets": ["b", "c"] })
+        );
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let page: Page<ElementPayload> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(4));
+        assert_eq!(page.page_size, 2);
+        assert!(page.data[0].data.get("num").is_some());
+        // Elements are responded in the same order than the ids only
+        let ids: Vec<_> = page.data.iter().map(|el| el.id.clone().unwrap()).collect();
+        assert_eq!(ids, vec!["el-2", "el-3"]);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_sets_operations_save_as() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        create_sets(&app, tid).await;
+        let req = post(&format!("/{tid}/sets/_ops"), json!({
+            "op": "intersection",
+            "sets": ["a", "b"],
+            "save_as": { "id": "a-and-b", "name": "A and B" },
+        }));
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::CREATED).await;
+        let set: Set = serde_json::from_slice(&body).unwrap();
+        assert_eq!(set.id, "a-and-b");
+        let req = get(&format!("/{tid}/sets/a-and-b/elements"));
+        let resp = call_service(&app, req).await;
+        let mut page: Page<String> = try_read_body_json(resp).await?;
+        page.data.sort();
+        assert_eq!(page.data, vec!["el-2", "el-3"]);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_sets_operations_set_not_found() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        create_sets(&app, tid).await;
+        let req = post(
+            &format!("/{tid}/sets/_ops"),
+            json!({ "op": "union", "sets": ["a", "z"] })
+        );
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::NOT_FOUND).await;
+        let error: ValidationErrorPayload = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.error, "set with id equals to \"z\" not found or was removed");
+        let req = post(&format!("/{tid}/sets/_ops"), json!({ "op": "union", "sets": [] }));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_sets_skip_elements_in_trash() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        create_sets(&app, tid).await;
+        let req = TestRequest::delete().uri(&format!("/{tid}/el-3?soft=true")).to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let req = get(&format!("/{tid}/sets/a/elements"));
+        let resp = call_service(&app, req).await;
+        let mut page: Page<String> = try_read_body_json(resp).await?;
+        page.data.sort();
+        assert_eq!(page.total, Some(3));
+        assert_eq!(page.data, vec!["el-0", "el-1", "el-2"]);
+        let req = post(
+            &format!("/{tid}/sets/_ops"),
+            json!({ "op": "union", "sets": ["a", "b"], "ids_only": true })
+        );
+        let resp = call_service(&app, req).await;
+        let page: Page<String> = try_read_body_json(resp).await?;
+        assert_eq!(page.data, vec!["el-0", "el-1", "el-2", "el-4"]);
+        assert_eq!(page.total, Some(4));
+        let req = post(&format!("/{tid}/sets/_ops"), json!({ "op": "union", "sets": ["b", "c"] }));
+        let resp = call_service(&app, req).await;
+        let page: Page<ElementPayload> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(3));
+        assert_eq!(page.data.len(), 3);
+        let req = post(&format!("/{tid}/sets/_ops"), json!({
+            "op": "intersection",
+            "sets": ["b", "c"],
+            "save_as": { "id": "b-and-c", "name": "B and C" },
+        }));
+        let resp = call_service(&app, req).await;
+        assert_status(resp, StatusCode::CREATED).await;
+        // Only the elements not in the trash are saved, and restored ones are listed again
+        let req = post(&format!("/{tid}/el-3/restore"), json!({}));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let req = get(&format!("/{tid}/sets/b-and-c/elements"));
+        let resp = call_service(&app, req).await;
+        let page: Page<String> = try_read_body_json(resp).await?;
+        assert_eq!(page.data, vec!["el-4"]);
+        let req = get(&format!("/{tid}/sets/a/elements"));
+        let resp = call_service(&app, req).await;
+        let page: Page<String> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(4));
+        Ok(())
+    }
+}
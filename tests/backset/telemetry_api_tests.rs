@@ -0,0 +1,66 @@
+#[cfg(test)]
+mod tests {
+    use crate::initialize;
+    use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::App;
+    use backset::app_server::AppServer;
+    use backset::telemetry::{parent_context, request_tracing, REQUEST_ID_HEADER};
+    use opentelemetry::trace::TraceContextExt;
+    use pretty_assertions::assert_eq;
+    use regex::Regex;
+
+    #[actix_web::test]
+    async fn test_request_id() {
+        let state = initialize().await;
+        let app = init_service(
+            App::new()
+                .configure(AppServer::config_app(state))
+                .wrap(from_fn(request_tracing))
+        ).await;
+        let uuid_re = Regex::new(r"^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-[0-9a-f]{4}-[0-9a-f]{12}$").unwrap();
+        let cases = [
+            // Generated if not passed or not valid, otherwise echoed
+            (None, None),
+            (Some("req-1234"), Some("req-1234")),
+            (Some(""), None),
+            (Some("with spaces"), None),
+        ];
+        for (req_id, expected) in cases {
+            let mut req = TestRequest::get().uri("/health");
+            if let Some(req_id) = req_id {
+                req = req.insert_header((REQUEST_ID_HEADER, req_id));
+            }
+            let resp = call_service(&app, req.to_request()).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let resp_id = resp.headers().get(REQUEST_ID_HEADER).unwrap().to_str().unwrap();
+            match expected {
+                Some(expected) => assert_eq!(resp_id, expected),
+                None => assert!(uuid_re.is_match(resp_id), "{resp_id}"),
+            }
+        }
+        // Also set in errors
+        let resp = call_service(&app, TestRequest::get().uri("/not-a-route/a/b").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert!(resp.headers().contains_key(REQUEST_ID_HEADER));
+    }
+
+    #[test]
+    fn test_parent_context() {
+        let mut headers = HeaderMap::new();
+        assert!(!parent_context(&headers).span().span_context().is_valid());
+        headers.insert(
+            HeaderName::from_static("traceparent"),
+            HeaderValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+        );
+        let context = parent_context(&headers);
+        let span_context = context.span().span_context().clone();
+        assert!(span_context.is_remote());
+        assert_eq!(span_context.trace_id().to_string(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(span_context.span_id().to_string(), "00f067aa0ba902b7");
+        headers.insert(HeaderName::from_static("traceparent"), HeaderValue::from_static("00-invalid"));
+        assert!(!parent_context(&headers).span().span_context().is_valid());
+    }
+}
@@ -1,18 +1,24 @@
 #[cfg(test)]
 mod tests {
-    use crate::{get, post, put, initialize};
+    use crate::{get, post, put, create_tenant, initialize};
     use actix_contrib_rest::page::Page;
     use actix_contrib_rest::result::{DeletedCount, ValidationErrorPayload};
     use actix_contrib_rest::test::assert_status;
     use actix_web::http::StatusCode;
-    use actix_web::test::{call_service, init_service, try_read_body_json, TestRequest};
+    use actix_web::test::{call_service, init_service, read_body, try_read_body_json, TestRequest};
     use actix_web::App;
     use backset::app_server::AppServer;
+    use backset::cursor::CursorPage;
+    use backset::tenants::export::ImportResult;
+    use backset::elements::model::Element;
     use backset::tenants::model::Tenant;
+    use backset::trash::purge;
     use backset::PAGE_SIZE;
+    use chrono::TimeDelta;
     use pretty_assertions::assert_eq;
     use rand::random;
     use serde_json::json;
+    use sqlx::Connection;
     use std::error::Error;
 
     #[actix_web::test]
@@ -134,6 +140,7 @@ mod tests {
     }
 
     #[actix_web::test]
+    #[allow(clippy::get_first)]
     async fn test_tenants_search() -> Result<(), Box<dyn Error>> {
         let state = initialize().await;
         let app = init_service(App::new().configure(AppServer::config_app(state))).await;
@@ -181,6 +188,41 @@ mod tests {
         Ok(())
     }
 
+    #[actix_web::test]
+    async fn test_tenants_get_with_cursor() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        let rand_for_test = random::<u32>();
+        for i in 0..5 {
+            let id = format!("cursor-{rand_for_test}-{i}");
+            let req = post("/tenants", json!({ "id": id, "name": format!("Cursor {id}") }));
+            let resp = call_service(&app, req).await;
+            assert_status(resp, StatusCode::CREATED).await;
+        }
+        let mut ids: Vec<String> = Vec::new();
+        let mut uri = format!("/tenants?q={rand_for_test}&page_size=2");
+        loop {
+            let req = get(uri.as_str());
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let page: CursorPage<Tenant> = try_read_body_json(resp).await?;
+            assert_eq!(page.page.total, Some(5));
+            ids.extend(page.page.data.into_iter().map(|t| t.id));
+            match page.next {
+                Some(next) => uri = format!("/tenants?q={rand_for_test}&page_size=2&after={next}"),
+                None => break,
+            }
+        }
+        let expected: Vec<String> = (0..5).map(|i| format!("cursor-{rand_for_test}-{i}")).collect();
+        assert_eq!(ids, expected);
+        let req = get(format!("/tenants?q={rand_for_test}&after=not-a-cursor").as_str());
+        let resp = call_service(&app, req).await;
+        let body = assert_status(resp, StatusCode::BAD_REQUEST).await;
+        let error: ValidationErrorPayload = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.code, Some("invalid_cursor".to_string()));
+        Ok(())
+    }
+
     #[actix_web::test]
     async fn test_tenants_post_already_exists() -> Result<(), Box<dyn Error>> {
         let state = initialize().await;
@@ -219,6 +261,7 @@ mod tests {
     }
 
     #[actix_web::test]
+    #[allow(clippy::assertions_on_constants)]
     async fn test_tenants_field_validations() -> Result<(), Box<dyn Error>> {
         let state = initialize().await;
         let app = init_service(App::new().configure(AppServer::config_app(state))).await;
@@ -421,4 +464,207 @@ mod tests {
         );
         Ok(())
     }
+
+    #[actix_web::test]
+    async fn test_tenants_export_and_import() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        let _id = random::<u32>();
+        let id = format!("exp-{_id}");
+        let req = post("/tenants", json!({"id": id, "name": format!("Exported {_id}"), "id_strategy": "sequence"}));
+        assert_status(call_service(&app, req).await, StatusCode::CREATED).await;
+        for i in 0..3 {
+            let req = post(format!("/{id}").as_str(), json!({"name": format!("El {i}")}));
+            assert_status(call_service(&app, req).await, StatusCode::CREATED).await;
+        }
+        let resp = call_service(&app, get(format!("/{id}/2").as_str())).await;
+        let element: serde_json::Value = try_read_body_json(resp).await?;
+        let req = post(format!("/{id}/sets").as_str(), json!({"id": "s1", "name": "S1"}));
+        assert_status(call_service(&app, req).await, StatusCode::CREATED).await;
+        let req = post(format!("/{id}/sets/s1/elements").as_str(), json!({"ids": ["1", "2"]}));
+        assert_status(call_service(&app, req).await, StatusCode::OK).await;
+        let req = post(format!("/{id}/1/relationships").as_str(), json!({"rel_type": "owns", "to_id": "2"}));
+        assert_status(call_service(&app, req).await, StatusCode::CREATED).await;
+        let schema = json!({"required": ["name"]});
+        let req = put(format!("/tenants/{id}/schema").as_str(), &schema);
+        assert_status(call_service(&app, req).await, StatusCode::OK).await;
+
+        let resp = call_service(&app, get(format!("/tenants/{id}/export").as_str())).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("content-type").unwrap(), "application/x-ndjson");
+        let export = read_body(resp).await;
+        let lines: Vec<serde_json::Value> = export.split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(serde_json::from_slice)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0]["type"], "tenant");
+        assert_eq!(lines[0]["tenant"]["id"], id.as_str());
+        assert_eq!(lines[2], json!({"type": "element", "element": element}));
+        let types: Vec<&str> = lines[4..].iter().map(|line| line["type"].as_str().unwrap()).collect();
+        assert_eq!(types, vec!["set", "set_element", "set_element", "relationship", "schema"]);
+        assert_eq!(lines[8], json!({"type": "schema", "set_id": null, "schema": schema}));
+
+        // Import the tenant after deleting it
+        let req = TestRequest::delete().uri(format!("/tenants/{id}?force=true").as_str()).to_request();
+        assert_status(call_service(&app, req).await, StatusCode::OK).await;
+        let import = |body: Vec<u8>| TestRequest::post()
+            .uri(format!("/tenants/{id}/import").as_str())
+            .insert_header(("content-type", "application/x-ndjson"))
+            .set_payload(body)
+            .to_request();
+        // The elements are validated with the schemas imported, and nothing is imported if not valid
+        let invalid_schema = json!({"type": "schema", "set_id": null, "schema": {"required": ["title"]}});
+        let body = format!("{}\n{}\n{invalid_schema}\n", lines[0], lines[1]).into_bytes();
+        let resp = call_service(&app, import(body)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let error: ValidationErrorPayload = try_read_body_json(resp).await?;
+        assert_eq!(error.code.as_deref(), Some("schema_validation"));
+        let mut forbidden = lines[1].clone();
+        forbidden["element"]["id"] = json!("sets");
+        let body = format!("{}\n{forbidden}", lines[0]).into_bytes();
+        let resp = call_service(&app, import(body)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let error: ValidationErrorPayload = try_read_body_json(resp).await?;
+        assert_eq!(error.code.as_deref(), Some("invalid_record"));
+        let resp = call_service(&app, import(export.to_vec())).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let result: ImportResult = try_read_body_json(resp).await?;
+        assert_eq!(result.tenant.id, id);
+        assert_eq!(result.elements, 3);
+        assert_eq!(result.sets, 1);
+        assert_eq!(result.relationships, 1);
+        let resp = call_service(&app, get(format!("/{id}/2").as_str())).await;
+        assert_eq!(try_read_body_json::<serde_json::Value, _>(resp).await?, element);
+        let resp = call_service(&app, get(format!("/{id}/sets/s1/elements").as_str())).await;
+        let page: Page<String> = try_read_body_json(resp).await?;
+        assert_eq!(page.data, vec!["1", "2"]);
+        let resp = call_service(&app, get(format!("/{id}/1/relationships").as_str())).await;
+        let page: Page<serde_json::Value> = try_read_body_json(resp).await?;
+        assert_eq!(page.total, Some(1));
+        assert_eq!(page.data[0]["to_id"], "2");
+        let resp = call_service(&app, get(format!("/tenants/{id}/schema").as_str())).await;
+        assert_eq!(try_read_body_json::<serde_json::Value, _>(resp).await?, schema);
+        // The sequence continues after the elements imported
+        let req = post(format!("/{id}").as_str(), json!({"name": "El 3"}));
+        let body = assert_status(call_service(&app, req).await, StatusCode::CREATED).await;
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body)?["id"], "4");
+
+        // Errors
+        let resp = call_service(&app, import(export.to_vec())).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body = format!("{}\n{}", lines[1], lines[0]).into_bytes();
+        let resp = call_service(&app, import(body)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let error: ValidationErrorPayload = try_read_body_json(resp).await?;
+        assert_eq!(error.code.as_deref(), Some("invalid_record"));
+        let resp = call_service(&app, get("/tenants/not-a-tenant/export")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_tenants_conditional_requests() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let app = init_service(App::new().configure(AppServer::config_app(state))).await;
+        let _id = random::<u32>();
+        let id = format!("cond-{_id}");
+        let req = post("/tenants", json!({"id": id, "name": format!("Conditional {_id}")}));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert_eq!(resp.headers().get("etag").unwrap(), "\"1\"");
+
+        let req = TestRequest::get()
+            .uri(format!("/tenants/{id}").as_str())
+            .insert_header(("if-none-match", "\"1\""))
+            .to_request();
+        assert_status(call_service(&app, req).await, StatusCode::NOT_MODIFIED).await;
+        let req = TestRequest::put()
+            .uri(format!("/tenants/{id}").as_str())
+            .insert_header(("if-match", "\"1\""))
+            .set_json(json!({"name": format!("Conditional {_id} v2")}))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("etag").unwrap(), "\"2\"");
+        let req = TestRequest::delete()
+            .uri(format!("/tenants/{id}").as_str())
+            .insert_header(("if-match", "\"1\""))
+            .to_request();
+        assert_status(call_service(&app, req).await, StatusCode::PRECONDITION_FAILED).await;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_tenants_soft_delete_restore_and_purge() -> Result<(), Box<dyn Error>> {
+        let state = initialize().await;
+        let tid = create_tenant(&state).await;
+        let old_tid = create_tenant(&state).await;
+        let app = init_service(App::new().configure(AppServer::config_app(state.clone()))).await;
+        for id in ["p1", "p2", "p3"] {
+            let req = post(format!("/{tid}").as_str(), json!({"id": id}));
+            assert_status(call_service(&app, req).await, StatusCode::CREATED).await;
+        }
+        let req = TestRequest::delete().uri(format!("/tenants/{tid}?soft=true").as_str()).to_request();
+        let body = assert_status(call_service(&app, req).await, StatusCode::OK).await;
+        let deleted_count: DeletedCount = serde_json::from_slice(&body)?;
+        assert_eq!(deleted_count.deleted, 1);
+
+        // The tenant and its elements are not accessible while in the trash
+        assert_status(call_service(&app, get(format!("/tenants/{tid}").as_str())).await, StatusCode::NOT_FOUND).await;
+        assert_status(call_service(&app, get(format!("/{tid}/p1").as_str())).await, StatusCode::NOT_FOUND).await;
+        let resp = call_service(&app, get(format!("/tenants/{tid}?include_deleted=true").as_str())).await;
+        let tenant: Tenant = try_read_body_json(resp).await?;
+        assert!(tenant.deleted_at.is_some());
+        let req = put(format!("/tenants/{tid}").as_str(), json!({"name": format!("{tid} New")}));
+        assert_status(call_service(&app, req).await, StatusCode::NOT_FOUND).await;
+        let req = post("/tenants", json!({"id": tid.to_string(), "name": format!("{tid} Other")}));
+        assert_status(call_service(&app, req).await, StatusCode::BAD_REQUEST).await;
+
+        let req = TestRequest::delete()
+            .uri(format!("/tenants/{tid}?soft=true").as_str())
+            .insert_header(("if-match", "\"2\""))
+            .to_request();
+        assert_status(call_service(&app, req).await, StatusCode::PRECONDITION_FAILED).await;
+
+        let resp = call_service(&app, post(format!("/tenants/{tid}/restore").as_str(), json!({}))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        // Moving to the trash and restoring are new versions
+        assert_eq!(resp.headers().get("etag").unwrap(), "\"3\"");
+        let tenant: Tenant = try_read_body_json(resp).await?;
+        assert!(tenant.deleted_at.is_none());
+        let req = TestRequest::put()
+            .uri(format!("/tenants/{tid}").as_str())
+            .insert_header(("if-match", "\"1\""))
+            .set_json(json!({"name": format!("{tid} New")}))
+            .to_request();
+        assert_status(call_service(&app, req).await, StatusCode::PRECONDITION_FAILED).await;
+        assert_status(call_service(&app, get(format!("/{tid}/p1").as_str())).await, StatusCode::OK).await;
+
+        // Only the tenants and elements in the trash before the age passed are purged
+        for id in ["p1", "p2"] {
+            let req = TestRequest::delete().uri(format!("/{tid}/{id}?soft=true").as_str()).to_request();
+            assert_status(call_service(&app, req).await, StatusCode::NO_CONTENT).await;
+        }
+        let req = TestRequest::delete().uri(format!("/tenants/{old_tid}?soft=true").as_str()).to_request();
+        assert_status(call_service(&app, req).await, StatusCode::OK).await;
+        let mut conn = state.get_conn().await?;
+        let mut tx = Connection::begin(&mut conn).await?;
+        let forty_days_ago = "NOW() - INTERVAL '40 days'";
+        sqlx::query(format!("UPDATE tenants SET deleted_at = {forty_days_ago} WHERE id = $1").as_str())
+            .bind(old_tid.to_string())
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(format!("UPDATE elements SET deleted_at = {forty_days_ago} WHERE tid = $1 AND id = 'p1'").as_str())
+            .bind(tid.to_string())
+            .execute(&mut *tx)
+            .await?;
+        let result = purge(&mut tx, TimeDelta::days(30)).await?;
+        assert!(result.tenants >= 1 && result.elements >= 1);
+        assert!(Tenant::get(&mut tx, old_tid.to_string().as_str(), true).await?.is_none());
+        assert!(Element::get(&mut tx, tid.to_string().as_str(), "p1", true).await?.is_none());
+        assert!(Element::get(&mut tx, tid.to_string().as_str(), "p2", true).await?.is_some());
+        state.commit_tx(tx).await?;
+        Ok(())
+    }
 }